@@ -0,0 +1,24 @@
+//! Packs a directory of assets into a single archive.
+//!
+//! Usage: cargo run --example pack_assets -- <asset directory> <output file>
+//!
+//! Load it in the game with `app.assets.mount_archive("assets.pak")`.
+
+use cobalt::assets::archive::ArchiveBuilder;
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.len() != 3 {
+        eprintln!("Usage: pack_assets <asset directory> <output file>");
+        std::process::exit(1);
+    }
+
+    let mut builder = ArchiveBuilder::new();
+    builder.add_dir(&args[1])?;
+    builder.write(&args[2])?;
+
+    println!("Packed {} files into {}", builder.len(), args[2]);
+
+    Ok(())
+}
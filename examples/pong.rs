@@ -1,8 +1,8 @@
 use std::time::{Duration, Instant};
 
 use cobalt::{
-    camera::Projection, renderer_2d::renderables::Rect, system::System, transform::Transform, App,
-    AppBuilder,
+    camera::Projection, renderer_2d::renderables::Rect, scene::Prefab, system::System,
+    transform::Transform, App, AppBuilder,
 };
 use hecs::Entity;
 use ultraviolet::{Vec2, Vec3};
@@ -59,6 +59,29 @@ fn main() {
             })
             .expect("Failed to create resource.");
 
+        // Templates for the paddles and the ball, the game components are added when they are spawned
+        let paddle = Prefab::from_components(
+            (
+                Transform::new(Vec3::zero(), Vec3::zero(), Vec3::new(1.5, 15.0, 1.0)),
+                Rect::new((1.0, 1.0, 1.0, 1.0)),
+            ),
+            app.scenes.registry(),
+            &app.assets,
+        )
+        .expect("Failed to create paddle prefab.");
+        app.scenes.add_prefab("paddle", paddle);
+
+        let ball = Prefab::from_components(
+            (
+                Transform::new(Vec3::zero(), Vec3::zero(), Vec3::new(3.0, 3.0, 1.0)),
+                Rect::new((1.0, 1.0, 1.0, 1.0)),
+            ),
+            app.scenes.registry(),
+            &app.assets,
+        )
+        .expect("Failed to create ball prefab.");
+        app.scenes.add_prefab("ball", ball);
+
         app.scenes.add(
             "scored",
             cobalt::scene::SceneGenerator::new(|scene, app| {
@@ -103,35 +126,53 @@ fn main() {
 
                 let viewport_size = scene.camera.as_ref().unwrap().viewport_size();
 
-                let left_paddle = scene.world.spawn((
-                    Transform::new(
-                        Vec3::new(-viewport_size.0 / 2.0 + 10.0, 0.0, 0.0),
-                        Vec3::zero(),
-                        Vec3::new(1.5, 15.0, 1.0),
-                    ),
-                    Paddle {
-                        up_key: cobalt::input::Key::KeyW,
-                        down_key: cobalt::input::Key::KeyS,
-                        speed: 75.0,
-                        moving: 0,
-                    },
-                    Rect::new((1.0, 1.0, 1.0, 1.0)),
-                ));
-
-                let right_paddle = scene.world.spawn((
-                    Transform::new(
-                        Vec3::new(viewport_size.0 / 2.0 - 10.0, 0.0, 0.0),
-                        Vec3::zero(),
-                        Vec3::new(1.5, 15.0, 1.0),
-                    ),
-                    Paddle {
-                        up_key: cobalt::input::Key::ArrowUp,
-                        down_key: cobalt::input::Key::ArrowDown,
-                        speed: 75.0,
-                        moving: 0,
-                    },
-                    Rect::new((1.0, 1.0, 1.0, 1.0)),
-                ));
+                let paddle = app.scenes.prefab("paddle").expect("Paddle prefab not found.");
+
+                let left_paddle = paddle
+                    .spawn_with(
+                        &mut scene.world,
+                        app.scenes.registry(),
+                        &mut app.assets,
+                        &app.window,
+                        |paddle| {
+                            if let Some(transform) = paddle.get_mut::<&mut Transform>() {
+                                *transform.position_mut() =
+                                    Vec3::new(-viewport_size.0 / 2.0 + 10.0, 0.0, 0.0);
+                            }
+
+                            paddle.add(Paddle {
+                                up_key: cobalt::input::Key::KeyW,
+                                down_key: cobalt::input::Key::KeyS,
+                                speed: 75.0,
+                                moving: 0,
+                            });
+                        },
+                    )
+                    .expect("Failed to spawn paddle.")
+                    .root;
+
+                let right_paddle = paddle
+                    .spawn_with(
+                        &mut scene.world,
+                        app.scenes.registry(),
+                        &mut app.assets,
+                        &app.window,
+                        |paddle| {
+                            if let Some(transform) = paddle.get_mut::<&mut Transform>() {
+                                *transform.position_mut() =
+                                    Vec3::new(viewport_size.0 / 2.0 - 10.0, 0.0, 0.0);
+                            }
+
+                            paddle.add(Paddle {
+                                up_key: cobalt::input::Key::ArrowUp,
+                                down_key: cobalt::input::Key::ArrowDown,
+                                speed: 75.0,
+                                moving: 0,
+                            });
+                        },
+                    )
+                    .expect("Failed to spawn paddle.")
+                    .root;
 
                 let initial_velocity = if app
                     .resources
@@ -153,17 +194,23 @@ fn main() {
                     Vec2::new(BALL_SPEED, 0.0)
                 };
 
-                let ball = scene.world.spawn((
-                    Transform::new(
-                        Vec3::new(0.0, 0.0, 0.0),
-                        Vec3::zero(),
-                        Vec3::new(3.0, 3.0, 1.0),
-                    ),
-                    Ball {
-                        velocity: initial_velocity,
-                    },
-                    Rect::new((1.0, 1.0, 1.0, 1.0)),
-                ));
+                let ball = app
+                    .scenes
+                    .prefab("ball")
+                    .expect("Ball prefab not found.")
+                    .spawn_with(
+                        &mut scene.world,
+                        app.scenes.registry(),
+                        &mut app.assets,
+                        &app.window,
+                        |ball| {
+                            ball.add(Ball {
+                                velocity: initial_velocity,
+                            });
+                        },
+                    )
+                    .expect("Failed to spawn ball.")
+                    .root;
 
                 let game = app.resources.get_resource_mut::<GameState>().unwrap();
 
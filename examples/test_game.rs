@@ -49,26 +49,26 @@ fn main() {
 
                 let sprite_texture = app
                     .assets
-                    .create_asset(Texture::load(
-                        &app.window,
-                        include_bytes!("../images/logo.png"),
-                    ))
+                    .create_asset(
+                        Texture::from_bytes(&app.window, include_bytes!("../images/logo.png"))
+                            .expect("Failed to load texture."),
+                    )
                     .expect("Failed to create asset.");
 
                     let bg_texture = app
                     .assets
-                    .create_asset(Texture::load(
-                        &app.window,
-                        include_bytes!("texture.png"),
-                    ))
+                    .create_asset(
+                        Texture::from_bytes(&app.window, include_bytes!("texture.png"))
+                            .expect("Failed to load texture."),
+                    )
                     .expect("Failed to create asset.");
 
                 let translucent_texture = app
                     .assets
-                    .create_asset(Texture::load(
-                        &app.window,
-                        include_bytes!("translucent.png"),
-                    ))
+                    .create_asset(
+                        Texture::from_bytes(&app.window, include_bytes!("translucent.png"))
+                            .expect("Failed to load texture."),
+                    )
                     .expect("Failed to create asset.");                
                 
                 scene.world.spawn((
@@ -90,8 +90,11 @@ fn main() {
                     Rigidbody2D::new(),
                 ));
 
+                let mut fps_text = Text::new("Cobalt Engine. Lorem ipsum dolor sit amet.", (200.0, 72.0), 20.0, 24.0);
+                fps_text.set_pixels_per_unit(48.0);
+
                 game.fps_text = Some(scene.world.spawn((
-                    Text::new("Cobalt Engine. Lorem ipsum dolor sit amet.", (200.0, 72.0), 20.0, 24.0),
+                    fps_text,
                     Transform::new(
                         Vec3::new(0.0, 0.0, 2.0),
                         Vec3::new(0.0, 0.0, 0.0),
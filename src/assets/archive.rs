@@ -0,0 +1,448 @@
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Component, Path},
+    sync::Mutex,
+};
+
+use ahash::AHashMap;
+
+/// First bytes of every archive file.
+const MAGIC: &[u8; 4] = b"CBPK";
+const VERSION: u32 = 1;
+
+/// Size of an entry in the table of contents without its path: path length, offset,
+/// stored size, size, compression, checksum flag and checksum.
+const ENTRY_FIELDS_SIZE: u64 = 2 + 8 + 8 + 8 + 1 + 1 + 4;
+
+/// Deflate can't shrink data by more than about 1032:1, larger sizes mean a corrupt table.
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+/// Extensions of formats that are already compressed, deflating them again only costs load time.
+const COMPRESSED_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "ogg", "mp3", "zip"];
+
+/// How a single entry is stored in the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Deflate,
+}
+
+impl Compression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> std::io::Result<Self> {
+        match byte {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            _ => Err(invalid_data(format!("Unknown compression method {}.", byte))),
+        }
+    }
+}
+
+/// Location and layout of one file inside an archive.
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    compression: Compression,
+    /// CRC32 of the uncompressed data, if the archive was built with integrity checks
+    checksum: Option<u32>,
+}
+
+impl ArchiveEntry {
+    /// True if the stored data lies within the file and the size could have come from it.
+    fn fits(&self, file_len: u64) -> bool {
+        let in_file = self
+            .offset
+            .checked_add(self.stored_size)
+            .map(|end| end <= file_len)
+            .unwrap_or(false);
+
+        let plausible_size = match self.compression {
+            Compression::None => self.size == self.stored_size,
+            Compression::Deflate => self.size <= self.stored_size.saturating_mul(MAX_DEFLATE_RATIO),
+        };
+
+        in_file && plausible_size
+    }
+}
+
+/// Turns a path into the form used as a key in archives: relative, with '/' separators.
+pub(crate) fn archive_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn invalid_data<S: Into<String>>(message: S) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.into())
+}
+
+/// Collects files and writes them into a single archive.
+/// This is the packaging step, run it as part of the build, see `examples/pack_assets.rs`.
+pub struct ArchiveBuilder {
+    files: Vec<(String, Vec<u8>, Compression)>,
+    integrity_check: bool,
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            integrity_check: true,
+        }
+    }
+
+    /// If true, a checksum of every entry is stored and verified when it is read.
+    /// Enabled by default.
+    pub fn with_integrity_check(mut self, enabled: bool) -> Self {
+        self.integrity_check = enabled;
+        self
+    }
+
+    /// Adds a file under the given path, replacing any file already added there.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P, bytes: Vec<u8>, compression: Compression) {
+        let path = archive_path(path.as_ref());
+
+        self.files.retain(|(existing, _, _)| *existing != path);
+        self.files.push((path, bytes, compression));
+    }
+
+    /// Adds every file in a directory and its subdirectories, with paths relative to it.
+    /// Files are deflated unless their format is already compressed.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> anyhow::Result<()> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_path_buf()];
+
+        while let Some(current) = pending.pop() {
+            for entry in std::fs::read_dir(&current)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                let compressed = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| COMPRESSED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                    .unwrap_or(false);
+
+                let compression = if compressed {
+                    Compression::None
+                } else {
+                    Compression::Deflate
+                };
+
+                let bytes = std::fs::read(&path)?;
+                self.add_file(path.strip_prefix(dir)?, bytes, compression);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of files added so far.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Builds the archive in memory.
+    /// Layout: magic, version, entry count, the table of contents, then the data of every entry.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut stored = Vec::with_capacity(self.files.len());
+
+        for (_, bytes, compression) in &self.files {
+            let data = match compression {
+                Compression::None => bytes.clone(),
+                Compression::Deflate => {
+                    let mut encoder = flate2::write::DeflateEncoder::new(
+                        Vec::new(),
+                        flate2::Compression::default(),
+                    );
+                    encoder.write_all(bytes)?;
+                    encoder.finish()?
+                }
+            };
+
+            stored.push(data);
+        }
+
+        let mut table = Vec::new();
+        let table_size: usize = self
+            .files
+            .iter()
+            .map(|(path, _, _)| path.len() + ENTRY_FIELDS_SIZE as usize)
+            .sum();
+        let mut offset = (MAGIC.len() + 4 + 4 + table_size) as u64;
+
+        for ((path, bytes, compression), data) in self.files.iter().zip(&stored) {
+            if path.len() > u16::MAX as usize {
+                return Err(anyhow::anyhow!("Archive path is too long: {}", path));
+            }
+
+            table.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            table.extend_from_slice(path.as_bytes());
+            table.extend_from_slice(&offset.to_le_bytes());
+            table.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            table.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            table.push(compression.to_byte());
+
+            if self.integrity_check {
+                table.push(1);
+                table.extend_from_slice(&crc32fast::hash(bytes).to_le_bytes());
+            } else {
+                table.push(0);
+                table.extend_from_slice(&0u32.to_le_bytes());
+            }
+
+            offset += data.len() as u64;
+        }
+
+        let mut archive = Vec::with_capacity(offset as usize);
+        archive.extend_from_slice(MAGIC);
+        archive.extend_from_slice(&VERSION.to_le_bytes());
+        archive.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        archive.extend_from_slice(&table);
+
+        for data in stored {
+            archive.extend_from_slice(&data);
+        }
+
+        Ok(archive)
+    }
+
+    /// Writes the archive to a file.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+
+        Ok(())
+    }
+}
+
+/// A packed archive of assets, opened for reading.
+/// Only the table of contents is kept in memory, entries are read from the file on demand.
+pub struct Archive {
+    file: Mutex<std::fs::File>,
+    entries: AHashMap<String, ArchiveEntry>,
+}
+
+impl Archive {
+    /// Opens an archive written by `ArchiveBuilder`.
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let mut file = std::fs::File::open(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open archive '{}': {}", path.display(), e))?;
+
+        // Sizes in the file are checked against its length before anything is allocated for them
+        let file_len = file.metadata()?.len();
+
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+
+        if &header[0..4] != MAGIC {
+            return Err(anyhow::anyhow!("'{}' is not an asset archive.", path.display()));
+        }
+
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(anyhow::anyhow!(
+                "Archive '{}' has version {}, only version {} is supported.",
+                path.display(),
+                version,
+                VERSION
+            ));
+        }
+
+        let count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if count as u64 * ENTRY_FIELDS_SIZE > file_len - header.len() as u64 {
+            return Err(anyhow::anyhow!(
+                "Archive '{}' is truncated, it can't hold {} entries.",
+                path.display(),
+                count
+            ));
+        }
+
+        let mut entries = AHashMap::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let mut path_len = [0u8; 2];
+            file.read_exact(&mut path_len)?;
+
+            let mut entry_path = vec![0u8; u16::from_le_bytes(path_len) as usize];
+            file.read_exact(&mut entry_path)?;
+            let entry_path = String::from_utf8(entry_path)
+                .map_err(|_| anyhow::anyhow!("Archive '{}' has an invalid path.", path.display()))?;
+
+            let mut fields = [0u8; 8 + 8 + 8 + 1 + 1 + 4];
+            file.read_exact(&mut fields)?;
+
+            let has_checksum = fields[25] != 0;
+
+            let entry = ArchiveEntry {
+                offset: u64::from_le_bytes(fields[0..8].try_into().unwrap()),
+                stored_size: u64::from_le_bytes(fields[8..16].try_into().unwrap()),
+                size: u64::from_le_bytes(fields[16..24].try_into().unwrap()),
+                compression: Compression::from_byte(fields[24])?,
+                checksum: has_checksum
+                    .then(|| u32::from_le_bytes(fields[26..30].try_into().unwrap())),
+            };
+
+            if !entry.fits(file_len) {
+                return Err(anyhow::anyhow!(
+                    "Archive '{}' is corrupted, '{}' doesn't fit in the file.",
+                    path.display(),
+                    entry_path
+                ));
+            }
+
+            entries.insert(entry_path, entry);
+        }
+
+        Ok(Self {
+            file: Mutex::new(file),
+            entries,
+        })
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries.contains_key(&archive_path(path.as_ref()))
+    }
+
+    /// Paths of every file in the archive.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|path| path.as_str())
+    }
+
+    /// Reads and decompresses a file, verifying its checksum if it has one.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Vec<u8>> {
+        let path = archive_path(path.as_ref());
+        let entry = self.entries.get(&path).ok_or(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("'{}' is not in the archive.", path),
+        ))?;
+
+        let mut stored = vec![0u8; entry.stored_size as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(entry.offset))?;
+            file.read_exact(&mut stored)?;
+        }
+
+        let data = match entry.compression {
+            Compression::None => stored,
+            Compression::Deflate => {
+                // Reading one byte past the size is enough to tell that the entry is too long
+                let mut data = Vec::with_capacity(entry.size as usize);
+                flate2::read::DeflateDecoder::new(stored.as_slice())
+                    .take(entry.size + 1)
+                    .read_to_end(&mut data)?;
+                data
+            }
+        };
+
+        if data.len() as u64 != entry.size {
+            return Err(invalid_data(format!("'{}' in the archive has the wrong size.", path)));
+        }
+
+        if let Some(checksum) = entry.checksum {
+            if crc32fast::hash(&data) != checksum {
+                return Err(invalid_data(format!(
+                    "'{}' in the archive is corrupted, its checksum doesn't match.",
+                    path
+                )));
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the archive to a file of its own in the temp directory.
+    fn write_temp(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("cobalt_{}_{}.pak", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let text = b"hello archive ".repeat(64);
+        let binary: Vec<u8> = (0..=255).collect();
+
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("textures/readme.txt", text.clone(), Compression::Deflate);
+        builder.add_file("data.bin", binary.clone(), Compression::None);
+
+        let path = write_temp("round_trip", &builder.to_bytes().unwrap());
+        let archive = Archive::open(&path).unwrap();
+
+        assert!(archive.contains("textures/readme.txt"));
+        assert_eq!(archive.read("textures/readme.txt").unwrap(), text);
+        assert_eq!(archive.read("data.bin").unwrap(), binary);
+        assert_eq!(
+            archive.read("missing.txt").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn corrupted_entry_fails_its_checksum() {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("data.bin", vec![1, 2, 3, 4], Compression::None);
+
+        // The data of the only entry is at the end of the file
+        let mut bytes = builder.to_bytes().unwrap();
+        *bytes.last_mut().unwrap() ^= 0xff;
+
+        let path = write_temp("checksum", &bytes);
+        let archive = Archive::open(&path).unwrap();
+
+        assert_eq!(
+            archive.read("data.bin").unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn truncated_table_is_rejected() {
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("data.bin", vec![1, 2, 3, 4], Compression::None);
+
+        let bytes = builder.to_bytes().unwrap();
+        let path = write_temp("truncated", &bytes[..bytes.len() - 8]);
+
+        assert!(Archive::open(&path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
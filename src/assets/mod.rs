@@ -0,0 +1,1124 @@
+use std::{
+    any::{Any, TypeId},
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc, RwLock, Weak,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use ahash::AHashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod archive;
+
+use archive::Archive;
+
+/// Typed identifier of an asset.
+/// Cheap to copy, so it can be stored in components or written to save files
+/// and turned back into a handle with `AssetManager::get_by_id`.
+/// Assets loaded from a path get an id derived from the path, so it is the same every run.
+pub struct AssetId<T> {
+    uuid: uuid::Uuid,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> AssetId<T> {
+    pub fn from_uuid(uuid: uuid::Uuid) -> Self {
+        Self {
+            uuid,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn uuid(&self) -> uuid::Uuid {
+        self.uuid
+    }
+}
+
+// Implemented by hand so T doesn't need to implement any of these traits
+impl<T> Clone for AssetId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for AssetId<T> {}
+
+impl<T> PartialEq for AssetId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.uuid == other.uuid
+    }
+}
+
+impl<T> Eq for AssetId<T> {}
+
+impl<T> Hash for AssetId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uuid.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for AssetId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AssetId<{}>({})", std::any::type_name::<T>(), self.uuid)
+    }
+}
+
+impl<T> Serialize for AssetId<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.uuid.serialize(serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for AssetId<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_uuid(uuid::Uuid::deserialize(deserializer)?))
+    }
+}
+
+/// Storage shared by every handle to an asset.
+/// The current value is swapped out when the asset is hot reloaded.
+struct AssetShared<T> {
+    id: uuid::Uuid,
+    value: RwLock<Arc<T>>,
+    /// Bumped every time the value is replaced
+    version: AtomicU64,
+}
+
+impl<T> AssetShared<T> {
+    fn new(id: uuid::Uuid, value: T) -> Self {
+        Self {
+            id,
+            value: RwLock::new(Arc::new(value)),
+            version: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Lets the asset manager keep track of assets without knowing their type.
+trait ErasedShared: Send + Sync {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+
+    fn id(&self) -> uuid::Uuid;
+
+    /// Replaces the value, handles see it after `Asset::refresh`
+    fn replace(&self, value: Box<dyn Any>);
+
+    fn value_type_id(&self) -> TypeId;
+}
+
+impl<T: Send + Sync + 'static> ErasedShared for AssetShared<T> {
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+
+    fn replace(&self, value: Box<dyn Any>) {
+        if let Ok(value) = value.downcast::<T>() {
+            *self.value.write().unwrap() = Arc::from(value);
+            self.version.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    fn value_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+}
+
+/// Lightweight handle to an asset managed by the asset manager
+/// Reference counted, the asset lives as long as any handle to it.
+/// Assets are immutable, use a type with interior mutability such as a `Mutex` for assets
+/// that need to change.
+pub struct Asset<T> {
+    shared: Arc<AssetShared<T>>,
+    /// The value when the handle was created or last refreshed
+    value: Arc<T>,
+    version: u64,
+}
+
+impl<T> Asset<T> {
+    fn from_shared(shared: Arc<AssetShared<T>>) -> Self {
+        let version = shared.version.load(Ordering::Acquire);
+        let value = shared.value.read().unwrap().clone();
+
+        Self {
+            shared,
+            value,
+            version,
+        }
+    }
+
+    pub fn id(&self) -> AssetId<T> {
+        AssetId::from_uuid(self.shared.id)
+    }
+
+    /// Creates a handle that doesn't keep the asset alive.
+    pub fn clone_weak(&self) -> AssetWeak<T> {
+        AssetWeak {
+            shared: Arc::downgrade(&self.shared),
+            id: self.shared.id,
+        }
+    }
+
+    /// True if the asset was hot reloaded since this handle was last refreshed.
+    pub fn is_outdated(&self) -> bool {
+        self.shared.version.load(Ordering::Acquire) != self.version
+    }
+
+    /// Switches the handle to the latest version of a hot reloaded asset.
+    /// Returns true if the value changed. Renderables call this every frame.
+    pub fn refresh(&mut self) -> bool {
+        let version = self.shared.version.load(Ordering::Acquire);
+
+        if version == self.version {
+            return false;
+        }
+
+        self.value = self.shared.value.read().unwrap().clone();
+        self.version = version;
+
+        true
+    }
+
+    /// True if both handles refer to the same asset.
+    pub fn ptr_eq(&self, other: &Asset<T>) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl<T> AsRef<T> for Asset<T> {
+    fn as_ref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Deref for Asset<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T> Clone for Asset<T> {
+    fn clone(&self) -> Self {
+        Asset {
+            shared: self.shared.clone(),
+            value: self.value.clone(),
+            version: self.version,
+        }
+    }
+}
+
+/// Handle that doesn't keep its asset alive, created with `Asset::clone_weak`.
+pub struct AssetWeak<T> {
+    shared: Weak<AssetShared<T>>,
+    id: uuid::Uuid,
+}
+
+impl<T> AssetWeak<T> {
+    /// Returns a strong handle, or None if the asset has been freed.
+    pub fn upgrade(&self) -> Option<Asset<T>> {
+        self.shared.upgrade().map(Asset::from_shared)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.shared.strong_count() > 0
+    }
+
+    pub fn id(&self) -> AssetId<T> {
+        AssetId::from_uuid(self.id)
+    }
+}
+
+impl<T> Clone for AssetWeak<T> {
+    fn clone(&self) -> Self {
+        AssetWeak {
+            shared: self.shared.clone(),
+            id: self.id,
+        }
+    }
+}
+
+enum RefCountMessage {
+    Drop(usize),
+    Clone(usize),
+}
+
+/// Handle to an asset that is being loaded in the background with `AssetManager::load_async`.
+/// Reference counted, the loaded asset is kept alive as long as a handle exists.
+pub struct LoadingAsset<T> {
+    id: usize,
+    ref_count_sender: Sender<RefCountMessage>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for LoadingAsset<T> {
+    fn clone(&self) -> Self {
+        self.ref_count_sender.send(RefCountMessage::Clone(self.id)).unwrap_or_else(|err| {
+            log::debug!("Failed to send ref count message: {:?}", err);
+        });
+
+        LoadingAsset {
+            id: self.id,
+            ref_count_sender: self.ref_count_sender.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for LoadingAsset<T> {
+    fn drop(&mut self) {
+        self.ref_count_sender.send(RefCountMessage::Drop(self.id)).unwrap_or_else(|err| {
+            log::debug!("Failed to send ref count message: {:?}", err);
+        });
+    }
+}
+
+/// State of a background load.
+#[derive(Debug, Clone)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    Failed(Arc<AssetError>),
+}
+
+impl LoadState {
+    pub fn is_loading(&self) -> bool {
+        matches!(self, LoadState::Loading)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        matches!(self, LoadState::Loaded)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, LoadState::Failed(_))
+    }
+}
+
+/// A set of background loads whose progress is tracked together, for example everything
+/// a scene needs before it can be shown.
+/// The group doesn't keep the assets alive, hold on to the `LoadingAsset` handles for that.
+#[derive(Debug, Clone, Default)]
+pub struct LoadGroup {
+    ids: Vec<usize>,
+}
+
+impl LoadGroup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<T>(&mut self, asset: &LoadingAsset<T>) {
+        if !self.ids.contains(&asset.id) {
+            self.ids.push(asset.id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// Errors that can happen while loading an asset from a path.
+#[derive(Debug)]
+pub enum AssetError {
+    /// The file couldn't be read
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// No loader is registered for the requested asset type
+    NoLoader { type_name: &'static str },
+    /// The file was read but the loader couldn't turn it into an asset
+    Decode { path: PathBuf, message: String },
+    /// The path is already loaded as a different asset type
+    TypeMismatch {
+        path: PathBuf,
+        type_name: &'static str,
+    },
+    /// The manager forgot the load before it finished, e.g. after `AssetManager::drop_all`
+    Released,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetError::Io { path, source } => {
+                write!(f, "Failed to read asset '{}': {}", path.display(), source)
+            }
+            AssetError::NoLoader { type_name } => {
+                write!(f, "No asset loader registered for {}.", type_name)
+            }
+            AssetError::Decode { path, message } => {
+                write!(f, "Failed to load asset '{}': {}", path.display(), message)
+            }
+            AssetError::TypeMismatch { path, type_name } => write!(
+                f,
+                "Asset '{}' is already loaded as a different type than {}.",
+                path.display(),
+                type_name
+            ),
+            AssetError::Released => write!(f, "The asset load was released before it finished."),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Passed to loaders, gives access to the file being loaded and the GPU.
+#[derive(Clone)]
+pub struct LoadContext {
+    path: PathBuf,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+}
+
+impl LoadContext {
+    /// Full path of the file being loaded, including the asset root.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub(crate) fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    /// Creates an `AssetError::Decode` for the file being loaded.
+    pub fn decode_error<E: fmt::Display>(&self, error: E) -> AssetError {
+        AssetError::Decode {
+            path: self.path.clone(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Turns the bytes of a file into an asset.
+/// Register one per asset type with `AssetManager::register_loader`.
+/// Loading happens in two steps so the slow part can run on a worker thread.
+pub trait AssetLoader: Send + Sync + 'static {
+    type Asset: Send + Sync + 'static;
+    /// Data produced by `decode` and handed to `create`.
+    type Decoded: Send + 'static;
+
+    /// Parses the file. Runs on a worker thread for background loads, so it must not use the GPU.
+    fn decode(&self, bytes: &[u8], context: &LoadContext) -> Result<Self::Decoded, AssetError>;
+
+    /// Creates the asset from the decoded data, always on the main thread.
+    fn create(&self, decoded: Self::Decoded, context: &LoadContext) -> Result<Self::Asset, AssetError>;
+}
+
+/// Object safe version of `AssetLoader` so loaders of different types can be stored together.
+trait ErasedAssetLoader: Send + Sync {
+    fn decode_any(&self, bytes: &[u8], context: &LoadContext) -> Result<Box<dyn Any + Send>, AssetError>;
+
+    fn create_any(&self, decoded: Box<dyn Any + Send>, context: &LoadContext) -> Result<Box<dyn Any>, AssetError>;
+
+    /// Wraps a value created by `create_any` in shared storage for handles.
+    fn share(&self, id: uuid::Uuid, value: Box<dyn Any>) -> Arc<dyn ErasedShared>;
+}
+
+impl<L: AssetLoader> ErasedAssetLoader for L {
+    fn decode_any(&self, bytes: &[u8], context: &LoadContext) -> Result<Box<dyn Any + Send>, AssetError> {
+        Ok(Box::new(self.decode(bytes, context)?))
+    }
+
+    fn create_any(&self, decoded: Box<dyn Any + Send>, context: &LoadContext) -> Result<Box<dyn Any>, AssetError> {
+        // Decoded data only ever comes from decode_any of the same loader
+        let decoded = decoded.downcast::<L::Decoded>().unwrap();
+
+        Ok(Box::new(self.create(*decoded, context)?))
+    }
+
+    fn share(&self, id: uuid::Uuid, value: Box<dyn Any>) -> Arc<dyn ErasedShared> {
+        Arc::new(AssetShared::new(id, *value.downcast::<L::Asset>().unwrap()))
+    }
+}
+
+/// Loads any file as raw bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesLoader;
+
+impl AssetLoader for BytesLoader {
+    type Asset = Vec<u8>;
+    type Decoded = Vec<u8>;
+
+    fn decode(&self, bytes: &[u8], _context: &LoadContext) -> Result<Vec<u8>, AssetError> {
+        Ok(bytes.to_vec())
+    }
+
+    fn create(&self, bytes: Vec<u8>, _context: &LoadContext) -> Result<Vec<u8>, AssetError> {
+        Ok(bytes)
+    }
+}
+
+/// Loads UTF-8 text files, such as config files, level data or shader sources.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextLoader;
+
+impl AssetLoader for TextLoader {
+    type Asset = String;
+    type Decoded = String;
+
+    fn decode(&self, bytes: &[u8], context: &LoadContext) -> Result<String, AssetError> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| context.decode_error(e))
+    }
+
+    fn create(&self, text: String, _context: &LoadContext) -> Result<String, AssetError> {
+        Ok(text)
+    }
+}
+
+/// How often files are checked for changes when hot reloading.
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+struct AssetEntry {
+    shared: Weak<dyn ErasedShared>,
+    /// Set for assets loaded from a path, so the path cache can be cleaned up with them
+    path: Option<PathBuf>,
+    /// Modification time of the file when it was last loaded, used for hot reloading
+    modified: Option<SystemTime>,
+}
+
+/// Book keeping for a background load, shared by all `LoadingAsset` handles to it.
+struct LoadSlot {
+    /// Relative to the asset root
+    path: PathBuf,
+    type_id: TypeId,
+    state: LoadState,
+    /// Keeps the loaded asset alive while the slot exists
+    asset: Option<Arc<dyn ErasedShared>>,
+    ref_count: usize,
+}
+
+/// Where files are read from.
+/// Cloned into worker threads for background loads.
+#[derive(Clone)]
+struct FileSource {
+    root: PathBuf,
+    /// Checked before the root directory
+    archive: Option<Arc<Archive>>,
+}
+
+impl FileSource {
+    fn in_archive(&self, path: &Path) -> bool {
+        self.archive
+            .as_ref()
+            .map(|archive| archive.contains(path))
+            .unwrap_or(false)
+    }
+
+    /// Path of the file as shown in errors and passed to loaders.
+    fn full_path(&self, path: &Path) -> PathBuf {
+        if self.in_archive(path) {
+            PathBuf::from(archive::archive_path(path))
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, AssetError> {
+        let result = match &self.archive {
+            Some(archive) if archive.contains(path) => archive.read(path),
+            _ => std::fs::read(self.root.join(path)),
+        };
+
+        result.map_err(|source| AssetError::Io {
+            path: self.full_path(path),
+            source,
+        })
+    }
+}
+
+/// Threads that read and decode background loads.
+const LOAD_THREADS: usize = 4;
+
+/// Sent from the worker threads once a file is decoded.
+type LoadResult = (usize, Result<Box<dyn Any + Send>, AssetError>);
+
+/// Id of an asset loaded from a path, stable across runs.
+fn path_id(path: &Path) -> uuid::Uuid {
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_URL, path.to_string_lossy().as_bytes())
+}
+
+/// Keeps track of every asset and loads new ones.
+/// The manager only holds weak references, assets are freed with their last handle.
+pub struct AssetManager {
+    assets: AHashMap<uuid::Uuid, AssetEntry>,
+    ref_count_sender: Sender<RefCountMessage>,
+    ref_count_receiver: Receiver<RefCountMessage>,
+
+    loaders: AHashMap<TypeId, Arc<dyn ErasedAssetLoader>>,
+    /// Paths relative to the root, mapped to the id of the loaded asset
+    paths: AHashMap<PathBuf, uuid::Uuid>,
+    source: FileSource,
+
+    loads: AHashMap<usize, LoadSlot>,
+    current_load_id: usize,
+    /// Paths that are being loaded in the background, mapped to their load id
+    loading_paths: AHashMap<PathBuf, usize>,
+    load_sender: Sender<LoadResult>,
+    load_receiver: Receiver<LoadResult>,
+    /// Reads and decodes background loads, a few threads however many loads are started
+    load_pool: rayon::ThreadPool,
+
+    hot_reload: bool,
+    last_hot_reload_check: Instant,
+
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+}
+
+impl AssetManager {
+    pub(crate) fn new(window: &crate::window::Window) -> AssetManager {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (load_tx, load_rx) = std::sync::mpsc::channel();
+
+        let mut manager = AssetManager {
+            assets: AHashMap::new(),
+            ref_count_sender: tx,
+            ref_count_receiver: rx,
+
+            loaders: AHashMap::new(),
+            paths: AHashMap::new(),
+            source: FileSource {
+                root: PathBuf::new(),
+                archive: None,
+            },
+
+            loads: AHashMap::new(),
+            current_load_id: 0,
+            loading_paths: AHashMap::new(),
+            load_sender: load_tx,
+            load_receiver: load_rx,
+            load_pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(LOAD_THREADS)
+                .thread_name(|i| format!("Asset loader {}", i))
+                .build()
+                .expect("Failed to start the asset loader threads."),
+
+            hot_reload: false,
+            last_hot_reload_check: Instant::now(),
+
+            device: window.device.clone(),
+            queue: window.queue.clone(),
+        };
+
+        manager.register_loader(crate::texture::TextureLoader::default());
+        manager.register_loader(BytesLoader);
+        manager.register_loader(TextLoader);
+        manager.register_loader(crate::shader::ShaderLoader);
+        #[cfg(feature = "renderer_2d")]
+        manager.register_loader(crate::renderer_2d::font::FontLoader);
+
+        manager
+    }
+
+    pub fn create_asset<T: Send + Sync + 'static>(&mut self, value: T) -> anyhow::Result<Asset<T>> {
+        let shared = Arc::new(AssetShared::new(uuid::Uuid::new_v4(), value));
+
+        self.track(shared.clone(), None);
+
+        Ok(Asset::from_shared(shared))
+    }
+
+    /// Returns a handle to a live asset, or None if it was freed or isn't a `T`.
+    pub fn get_by_id<T: Send + Sync + 'static>(&self, id: AssetId<T>) -> Option<Asset<T>> {
+        self.handle(id.uuid)
+    }
+
+    /// Registers the loader used by `AssetManager::load` for its asset type.
+    /// Replaces any loader previously registered for the same type.
+    pub fn register_loader<L: AssetLoader>(&mut self, loader: L) {
+        self.loaders
+            .insert(TypeId::of::<L::Asset>(), Arc::new(loader));
+    }
+
+    /// Sets the directory paths passed to `AssetManager::load` are relative to.
+    /// Defaults to the working directory.
+    pub fn set_root<P: AsRef<Path>>(&mut self, root: P) {
+        self.source.root = root.as_ref().to_path_buf();
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.source.root
+    }
+
+    /// Reads assets from a packed archive, see `archive::ArchiveBuilder`.
+    /// Files that aren't in the archive are still read from the asset root.
+    /// Pass None to go back to loose files only.
+    pub fn set_archive(&mut self, archive: Option<Archive>) {
+        self.source.archive = archive.map(Arc::new);
+    }
+
+    /// Opens an archive file and reads assets from it.
+    pub fn mount_archive<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        self.set_archive(Some(Archive::open(path)?));
+
+        Ok(())
+    }
+
+    pub fn archive(&self) -> Option<&Archive> {
+        self.source.archive.as_deref()
+    }
+
+    /// Reads a file relative to the asset root, from the mounted archive if it is in there.
+    pub fn read_file(&self, path: impl AsRef<Path>) -> Result<Vec<u8>, AssetError> {
+        self.source.read(path.as_ref())
+    }
+
+    /// Path an asset was loaded from, relative to the asset root.
+    /// None for assets that were created in code or have been freed.
+    pub fn asset_path<T>(&self, id: AssetId<T>) -> Option<&Path> {
+        self.assets.get(&id.uuid())?.path.as_deref()
+    }
+
+    fn context(&self, path: &Path) -> LoadContext {
+        LoadContext {
+            path: self.source.full_path(path),
+            device: self.device.clone(),
+            queue: self.queue.clone(),
+        }
+    }
+
+    fn loader<T: 'static>(&self) -> Result<Arc<dyn ErasedAssetLoader>, AssetError> {
+        self.loaders
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .ok_or(AssetError::NoLoader {
+                type_name: std::any::type_name::<T>(),
+            })
+    }
+
+    /// The live asset loaded from a path, if there is one.
+    fn cached(&self, path: &Path) -> Option<Arc<dyn ErasedShared>> {
+        let id = self.paths.get(path)?;
+
+        self.assets.get(id)?.shared.upgrade()
+    }
+
+    /// Loads an asset from a file relative to the asset root, blocking until it is ready.
+    /// If the path is already loaded, a new handle to the existing asset is returned instead.
+    pub fn load<T: Send + Sync + 'static>(&mut self, path: impl AsRef<Path>) -> Result<Asset<T>, AssetError> {
+        let path = path.as_ref();
+
+        if let Some(shared) = self.cached(path) {
+            return downcast_shared(shared).ok_or(AssetError::TypeMismatch {
+                path: path.to_path_buf(),
+                type_name: std::any::type_name::<T>(),
+            });
+        }
+
+        let loader = self.loader::<T>()?;
+        let context = self.context(path);
+
+        let bytes = self.source.read(path)?;
+
+        let decoded = loader.decode_any(&bytes, &context)?;
+        let value = loader.create_any(decoded, &context)?;
+
+        let shared = loader.share(path_id(path), value);
+        self.track(shared.clone(), Some(path.to_path_buf()));
+
+        Ok(downcast_shared(shared).unwrap())
+    }
+
+    /// Starts loading an asset from a file relative to the asset root on a worker thread.
+    /// The file is read and decoded in the background, GPU resources are created on the main
+    /// thread at the start of a frame. Use `AssetManager::get` once the state is `Loaded`.
+    pub fn load_async<T: Send + Sync + 'static>(&mut self, path: impl AsRef<Path>) -> LoadingAsset<T> {
+        let path = path.as_ref();
+
+        // Join a load of the same file that is still running
+        if let Some(&load_id) = self.loading_paths.get(path) {
+            let slot = self.loads.get_mut(&load_id).unwrap();
+
+            if slot.type_id == TypeId::of::<T>() {
+                slot.ref_count += 1;
+                return self.loading_handle(load_id);
+            }
+        }
+
+        let load_id = self.current_load_id;
+        self.current_load_id += 1;
+
+        let mut slot = LoadSlot {
+            path: path.to_path_buf(),
+            type_id: TypeId::of::<T>(),
+            state: LoadState::Loading,
+            asset: None,
+            ref_count: 1,
+        };
+
+        if let Some(shared) = self.cached(path) {
+            // Already loaded, the slot just holds another reference
+            if shared.value_type_id() == TypeId::of::<T>() {
+                slot.state = LoadState::Loaded;
+                slot.asset = Some(shared);
+            } else {
+                slot.state = LoadState::Failed(Arc::new(AssetError::TypeMismatch {
+                    path: path.to_path_buf(),
+                    type_name: std::any::type_name::<T>(),
+                }));
+            }
+        } else {
+            match self.loader::<T>() {
+                Ok(loader) => {
+                    let context = self.context(path);
+                    let source = self.source.clone();
+                    let relative_path = path.to_path_buf();
+                    let sender = self.load_sender.clone();
+
+                    self.load_pool.spawn(move || {
+                        // A panicking loader fails the load, otherwise `wait_for` would never return
+                        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                            source
+                                .read(&relative_path)
+                                .and_then(|bytes| loader.decode_any(&bytes, &context))
+                        }))
+                        .unwrap_or_else(|panic| {
+                            let message = panic
+                                .downcast_ref::<&str>()
+                                .map(|message| message.to_string())
+                                .or_else(|| panic.downcast_ref::<String>().cloned())
+                                .unwrap_or_else(|| "unknown panic".to_string());
+
+                            Err(context.decode_error(format!("The loader panicked: {}", message)))
+                        });
+
+                        // The manager is gone if this fails, nothing left to do
+                        let _ = sender.send((load_id, result));
+                    });
+
+                    self.loading_paths.insert(path.to_path_buf(), load_id);
+                }
+                Err(e) => slot.state = LoadState::Failed(Arc::new(e)),
+            }
+        }
+
+        self.loads.insert(load_id, slot);
+
+        self.loading_handle(load_id)
+    }
+
+    /// Finishes background loads whose files have been decoded.
+    /// Called by the app at the start of every frame.
+    pub fn update_loads(&mut self) {
+        while let Ok((load_id, result)) = self.load_receiver.try_recv() {
+            self.finish_load(load_id, result);
+        }
+    }
+
+    /// Blocks until every load in the group has finished or failed.
+    pub fn wait_for(&mut self, group: &LoadGroup) {
+        while self.group_state(group).is_loading() {
+            match self.load_receiver.recv() {
+                Ok((load_id, result)) => self.finish_load(load_id, result),
+                // Can't happen while the manager holds a sender
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn finish_load(&mut self, load_id: usize, result: Result<Box<dyn Any + Send>, AssetError>) {
+        // All handles were dropped while loading
+        let Some(slot) = self.loads.get(&load_id) else {
+            return;
+        };
+
+        let path = slot.path.clone();
+        let type_id = slot.type_id;
+
+        if self.loading_paths.get(&path) == Some(&load_id) {
+            self.loading_paths.remove(&path);
+        }
+
+        let result = if let Some(shared) = self.cached(&path) {
+            // Loaded synchronously in the meantime, use that asset instead
+            Ok(shared)
+        } else {
+            result.and_then(|decoded| {
+                let loader = self.loaders.get(&type_id).unwrap().clone();
+                let context = self.context(&path);
+                let value = loader.create_any(decoded, &context)?;
+
+                let shared = loader.share(path_id(&path), value);
+                self.track(shared.clone(), Some(path.clone()));
+
+                Ok(shared)
+            })
+        };
+
+        let slot = self.loads.get_mut(&load_id).unwrap();
+
+        match result {
+            Ok(shared) => {
+                slot.state = LoadState::Loaded;
+                slot.asset = Some(shared);
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                slot.state = LoadState::Failed(Arc::new(e));
+            }
+        }
+    }
+
+    /// Enables or disables hot reloading, meant for development.
+    /// While enabled, files loaded from a path are checked for changes twice a second and
+    /// reloaded in place. Handles switch to the new version on `Asset::refresh`, which the
+    /// built-in renderables call every frame.
+    /// Only loose files are watched. Files in the mounted archive are skipped even if a loose copy
+    /// exists, since that copy is never what gets loaded.
+    /// Shaders given to `Renderer::set_shader` are reloaded with their pipelines,
+    /// the built-in shaders are compiled into the engine.
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.hot_reload
+    }
+
+    /// Reloads changed files if hot reloading is enabled.
+    /// Called by the app at the start of every frame.
+    pub fn update_hot_reload(&mut self) {
+        if !self.hot_reload || self.last_hot_reload_check.elapsed() < HOT_RELOAD_INTERVAL {
+            return;
+        }
+        self.last_hot_reload_check = Instant::now();
+
+        let changed: Vec<(PathBuf, uuid::Uuid)> = self
+            .paths
+            .iter()
+            // Archived files are read from the archive, a loose copy next to it is never loaded
+            .filter(|(path, _)| !self.source.in_archive(path))
+            .filter(|(path, id)| {
+                let modified = modified_time(&self.source.root.join(path));
+                modified.is_some() && modified != self.assets[*id].modified
+            })
+            .map(|(path, id)| (path.clone(), *id))
+            .collect();
+
+        for (path, id) in changed {
+            let context = self.context(&path);
+            let modified = modified_time(&self.source.root.join(&path));
+            let entry = self.assets.get_mut(&id).unwrap();
+
+            // Even if the reload fails, don't try again until the file changes again
+            entry.modified = modified;
+
+            let Some(shared) = entry.shared.upgrade() else {
+                continue;
+            };
+
+            let Some(loader) = self.loaders.get(&shared.value_type_id()) else {
+                continue;
+            };
+
+            let result = self
+                .source
+                .read(&path)
+                .and_then(|bytes| loader.decode_any(&bytes, &context))
+                .and_then(|decoded| loader.create_any(decoded, &context));
+
+            match result {
+                Ok(value) => {
+                    shared.replace(value);
+                    log::info!("Reloaded asset '{}'.", path.display());
+                }
+                Err(e) => log::error!("Failed to reload asset: {}", e),
+            }
+        }
+    }
+
+    /// State of a background load.
+    pub fn state<T>(&self, asset: &LoadingAsset<T>) -> LoadState {
+        self.slot_state(asset.id)
+    }
+
+    /// Loads the manager no longer knows about failed with `AssetError::Released`.
+    fn slot_state(&self, load_id: usize) -> LoadState {
+        self.loads
+            .get(&load_id)
+            .map(|slot| slot.state.clone())
+            .unwrap_or_else(|| LoadState::Failed(Arc::new(AssetError::Released)))
+    }
+
+    /// Returns a handle to the asset once it is loaded.
+    pub fn get<T: Send + Sync + 'static>(&self, asset: &LoadingAsset<T>) -> Option<Asset<T>> {
+        let shared = self.loads.get(&asset.id)?.asset.clone()?;
+
+        downcast_shared(shared)
+    }
+
+    /// Fraction of the group that has finished loading, failed loads count as finished.
+    /// An empty group is complete.
+    pub fn progress(&self, group: &LoadGroup) -> f32 {
+        if group.is_empty() {
+            return 1.0;
+        }
+
+        let finished = group
+            .ids
+            .iter()
+            .filter(|id| {
+                self.loads
+                    .get(id)
+                    .map(|slot| !slot.state.is_loading())
+                    .unwrap_or(true)
+            })
+            .count();
+
+        finished as f32 / group.len() as f32
+    }
+
+    /// `Loading` while any load in the group is still running, then `Failed` if any of them
+    /// failed, otherwise `Loaded`. Loads whose handles were all dropped count as failed.
+    pub fn group_state(&self, group: &LoadGroup) -> LoadState {
+        let states: Vec<LoadState> = group.ids.iter().map(|&id| self.slot_state(id)).collect();
+
+        if states.iter().any(|state| state.is_loading()) {
+            return LoadState::Loading;
+        }
+
+        states
+            .into_iter()
+            .find(|state| state.is_failed())
+            .unwrap_or(LoadState::Loaded)
+    }
+
+    /// True if the path was loaded and the asset is still alive.
+    pub fn is_loaded(&self, path: impl AsRef<Path>) -> bool {
+        self.cached(path.as_ref()).is_some()
+    }
+
+    /// Starts tracking a new asset, with the modification time of its file if it has one.
+    fn track(&mut self, shared: Arc<dyn ErasedShared>, path: Option<PathBuf>) {
+        let id = shared.id();
+        let modified = path
+            .as_ref()
+            .and_then(|path| modified_time(&self.source.root.join(path)));
+
+        if let Some(path) = &path {
+            self.paths.insert(path.clone(), id);
+        }
+
+        self.assets.insert(
+            id,
+            AssetEntry {
+                shared: Arc::downgrade(&shared),
+                path,
+                modified,
+            },
+        );
+    }
+
+    /// Creates a handle to a live asset, or None if it was freed or isn't a `T`.
+    fn handle<T: Send + Sync + 'static>(&self, id: uuid::Uuid) -> Option<Asset<T>> {
+        let shared = self.assets.get(&id)?.shared.upgrade()?;
+
+        downcast_shared(shared)
+    }
+
+    fn loading_handle<T>(&self, load_id: usize) -> LoadingAsset<T> {
+        LoadingAsset {
+            id: load_id,
+            ref_count_sender: self.ref_count_sender.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Forgets about every asset.
+    /// Assets that still have handles stay alive until those are dropped.
+    pub fn drop_all(&mut self) {
+        // Live assets here used to be freed from under their handles, point them out
+        #[cfg(debug_assertions)]
+        for (id, entry) in &self.assets {
+            if entry.shared.strong_count() > 0 {
+                log::warn!(
+                    "Asset {} is still in use after drop_all, it will be freed with its last handle.",
+                    entry.path.as_ref().map(|p| p.display().to_string()).unwrap_or(id.to_string())
+                );
+            }
+        }
+
+        self.assets.clear();
+        self.paths.clear();
+        self.loads.clear();
+        self.loading_paths.clear();
+    }
+
+    /// Releases background loads whose handles were dropped and forgets freed assets.
+    pub fn update_ref_counts(&mut self) {
+        while let Ok(msg) = self.ref_count_receiver.try_recv() {
+            match msg {
+                RefCountMessage::Drop(load_id) => {
+                    if let Some(slot) = self.loads.get_mut(&load_id) {
+                        slot.ref_count -= 1;
+                        if slot.ref_count == 0 {
+                            let slot = self.loads.remove(&load_id).unwrap();
+
+                            if self.loading_paths.get(&slot.path) == Some(&load_id) {
+                                self.loading_paths.remove(&slot.path);
+                            }
+                        }
+                    }
+                }
+                RefCountMessage::Clone(load_id) => {
+                    if let Some(slot) = self.loads.get_mut(&load_id) {
+                        slot.ref_count += 1;
+                    }
+                }
+            }
+        }
+
+        let paths = &mut self.paths;
+        self.assets.retain(|_, entry| {
+            let alive = entry.shared.strong_count() > 0;
+
+            if let (false, Some(path)) = (alive, &entry.path) {
+                paths.remove(path);
+            }
+
+            alive
+        });
+    }
+}
+
+fn downcast_shared<T: Send + Sync + 'static>(shared: Arc<dyn ErasedShared>) -> Option<Asset<T>> {
+    shared
+        .into_any()
+        .downcast::<AssetShared<T>>()
+        .ok()
+        .map(Asset::from_shared)
+}
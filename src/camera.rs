@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use ultraviolet::{Mat4, Vec4};
 use wgpu::util::DeviceExt;
 
@@ -14,6 +15,7 @@ pub struct Camera {
     cached_view_matrix: Option<ultraviolet::Mat4>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum Projection {
     Perspective {
         fov: f32,
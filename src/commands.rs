@@ -0,0 +1,123 @@
+use crate::{hierarchy, scene::ScenesManager, App};
+
+type Command = Box<dyn FnOnce(&mut App) -> anyhow::Result<()>>;
+
+/// Changes to the app that are queued by systems and applied after the system returns.
+/// This keeps the worlds and resources a system is looking at from changing under it.
+/// Scene changes made through `app.scenes` are queued the same way and applied after these.
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub(crate) fn new() -> Self {
+        Self { queue: Vec::new() }
+    }
+
+    /// Spawns an entity in the scene that is current when the command is applied.
+    pub fn spawn(&mut self, bundle: impl hecs::DynamicBundle + 'static) {
+        self.add(move |app| {
+            let scene = app
+                .scenes
+                .current_scene_mut()
+                .ok_or(anyhow::anyhow!("No scene loaded to spawn in."))?;
+
+            scene.world.spawn(bundle);
+
+            Ok(())
+        });
+    }
+
+    /// Spawns an entity in a loaded scene, e.g. an additive one.
+    pub fn spawn_in<S: Into<String>>(&mut self, scene: S, bundle: impl hecs::DynamicBundle + 'static) {
+        let name = scene.into();
+
+        self.add(move |app| {
+            let scene = app
+                .scenes
+                .scene_mut(&name)
+                .ok_or(anyhow::anyhow!("Scene '{}' is not loaded.", name))?;
+
+            scene.world.spawn(bundle);
+
+            Ok(())
+        });
+    }
+
+    /// Despawns an entity and its children from the current scene.
+    pub fn despawn(&mut self, entity: hecs::Entity) {
+        self.add(move |app| {
+            let scene = app
+                .scenes
+                .current_scene_mut()
+                .ok_or(anyhow::anyhow!("No scene loaded to despawn from."))?;
+
+            hierarchy::despawn_recursive(&mut scene.world, entity)
+        });
+    }
+
+    /// Despawns an entity and its children from a loaded scene.
+    pub fn despawn_in<S: Into<String>>(&mut self, scene: S, entity: hecs::Entity) {
+        let name = scene.into();
+
+        self.add(move |app| {
+            let scene = app
+                .scenes
+                .scene_mut(&name)
+                .ok_or(anyhow::anyhow!("Scene '{}' is not loaded.", name))?;
+
+            hierarchy::despawn_recursive(&mut scene.world, entity)
+        });
+    }
+
+    /// Creates a resource, see `ResourceManager::create_resource`.
+    pub fn create_resource<T: 'static>(&mut self, value: T) {
+        self.add(move |app| app.resources.create_resource(value));
+    }
+
+    /// Queues any change to the app. Errors are logged when the command is applied.
+    pub fn add(&mut self, command: impl FnOnce(&mut App) -> anyhow::Result<()> + 'static) {
+        self.queue.push(Box::new(command));
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// How often `apply` goes through the queues before it leaves the rest for the next frame.
+const MAX_APPLY_ROUNDS: usize = 16;
+
+/// Applies the queued commands, then the queued scene changes.
+/// Commands can queue more commands, e.g. scene generators that spawn entities,
+/// so this runs until both queues are empty. Commands that keep queuing more,
+/// e.g. a scene generator that loads its own scene, are cut off and continue next time.
+pub(crate) fn apply(app: &mut App) {
+    for _ in 0..MAX_APPLY_ROUNDS {
+        let commands = std::mem::take(&mut app.commands.queue);
+        let scene_changes = app.scenes.has_pending_changes();
+
+        if commands.is_empty() && !scene_changes {
+            return;
+        }
+
+        for command in commands {
+            if let Err(e) = command(app) {
+                log::error!("Failed to apply command: {}", e);
+            }
+        }
+
+        ScenesManager::apply_changes(app);
+    }
+
+    if !app.commands.is_empty() || app.scenes.has_pending_changes() {
+        log::error!(
+            "Commands kept queuing more commands or scene changes after {} rounds, the rest are applied later.",
+            MAX_APPLY_ROUNDS
+        );
+    }
+}
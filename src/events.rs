@@ -0,0 +1,186 @@
+use std::{
+    any::{Any, TypeId},
+    marker::PhantomData,
+    path::PathBuf,
+};
+
+use ahash::AHashMap;
+
+struct EventInstance<T> {
+    id: u64,
+    event: T,
+}
+
+/// The events of one type, double buffered so every event lives for this frame and the next.
+struct EventQueue<T> {
+    previous: Vec<EventInstance<T>>,
+    current: Vec<EventInstance<T>>,
+    next_id: u64,
+}
+
+impl<T> EventQueue<T> {
+    fn iter(&self) -> impl Iterator<Item = &EventInstance<T>> {
+        self.previous.iter().chain(self.current.iter())
+    }
+}
+
+trait EventStorage {
+    fn update(&mut self);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> EventStorage for EventQueue<T> {
+    fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    fn clear(&mut self) {
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Game defined events sent between systems.
+/// Any `T: 'static` can be an event, events can be read during the frame they are sent and the next one.
+pub struct Events {
+    queues: AHashMap<TypeId, Box<dyn EventStorage>>,
+}
+
+impl Events {
+    pub(crate) fn new() -> Self {
+        Self {
+            queues: AHashMap::new(),
+        }
+    }
+
+    pub fn send<T: 'static>(&mut self, event: T) {
+        let queue = self
+            .queues
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(EventQueue::<T> {
+                    previous: Vec::new(),
+                    current: Vec::new(),
+                    next_id: 0,
+                })
+            })
+            .as_any_mut()
+            .downcast_mut::<EventQueue<T>>()
+            .expect("Event queue has the wrong type.");
+
+        queue.current.push(EventInstance {
+            id: queue.next_id,
+            event,
+        });
+        queue.next_id += 1;
+    }
+
+    /// Every event of the type sent this frame or the last one, oldest first.
+    /// Use an `EventReader` to only see each event once.
+    pub fn iter<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.queue::<T>()
+            .into_iter()
+            .flat_map(|queue| queue.iter())
+            .map(|instance| &instance.event)
+    }
+
+    /// Drops the stored events of the type.
+    pub fn clear<T: 'static>(&mut self) {
+        if let Some(queue) = self.queues.get_mut(&TypeId::of::<T>()) {
+            queue.clear();
+        }
+    }
+
+    fn queue<T: 'static>(&self) -> Option<&EventQueue<T>> {
+        self.queues
+            .get(&TypeId::of::<T>())
+            .and_then(|queue| queue.as_any().downcast_ref::<EventQueue<T>>())
+    }
+
+    /// Drops the events from the last frame, called at the start of every frame.
+    pub(crate) fn update(&mut self) {
+        for queue in self.queues.values_mut() {
+            queue.update();
+        }
+    }
+}
+
+/// Remembers which events a system has already seen.
+/// Keep one per system, e.g. captured by the system's closure.
+pub struct EventReader<T> {
+    next_id: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> EventReader<T> {
+    /// A reader that will see every event that is still stored.
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The events sent since the last call, oldest first.
+    pub fn read<'a>(&mut self, events: &'a Events) -> impl Iterator<Item = &'a T> {
+        let queue = events.queue::<T>();
+        let start = self.next_id;
+
+        if let Some(queue) = queue {
+            self.next_id = queue.next_id;
+        }
+
+        queue
+            .into_iter()
+            .flat_map(|queue| queue.iter())
+            .filter(move |instance| instance.id >= start)
+            .map(|instance| &instance.event)
+    }
+}
+
+impl<T: 'static> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The window was moved, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowMoved {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The DPI of the monitor changed, or the window moved to another monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleFactorChanged {
+    pub scale_factor: f64,
+}
+
+/// A file was dropped on the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDropped {
+    pub path: PathBuf,
+}
+
+/// A scene was loaded, pushed or loaded additively.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneLoaded {
+    pub name: String,
+}
+
+/// A scene was unloaded, popped or replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneUnloaded {
+    pub name: String,
+}
@@ -0,0 +1,219 @@
+use hecs::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::transform::Transform;
+
+/// The entity this entity is attached to.
+/// Its `Transform` is relative to the parent's. Change it with `set_parent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+/// The entities attached to this entity, in the order they were added.
+/// Kept in sync with `Parent` by the functions in this module.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.0.contains(&entity)
+    }
+}
+
+pub fn parent(world: &hecs::World, entity: Entity) -> Option<Entity> {
+    world.get::<&Parent>(entity).ok().map(|parent| parent.0)
+}
+
+/// The children of an entity, empty if it has none.
+pub fn children(world: &hecs::World, entity: Entity) -> Vec<Entity> {
+    world
+        .get::<&Children>(entity)
+        .map(|children| children.0.clone())
+        .unwrap_or_default()
+}
+
+/// True if `ancestor` is the entity itself or one of its parents.
+pub fn is_ancestor(world: &hecs::World, ancestor: Entity, entity: Entity) -> bool {
+    let mut current = Some(entity);
+
+    while let Some(entity) = current {
+        if entity == ancestor {
+            return true;
+        }
+
+        current = parent(world, entity);
+    }
+
+    false
+}
+
+/// Attaches the entity to a parent, or detaches it if the parent is None.
+/// The local transform is kept, so the entity moves along with its new parent.
+pub fn set_parent(world: &mut hecs::World, entity: Entity, parent: Option<Entity>) -> anyhow::Result<()> {
+    if !world.contains(entity) {
+        return Err(anyhow::anyhow!("Entity {:?} doesn't exist.", entity));
+    }
+
+    if let Some(parent) = parent {
+        if !world.contains(parent) {
+            return Err(anyhow::anyhow!("Parent {:?} doesn't exist.", parent));
+        }
+
+        if is_ancestor(world, entity, parent) {
+            return Err(anyhow::anyhow!(
+                "Can't attach {:?} to {:?}, it would become its own ancestor.",
+                entity,
+                parent
+            ));
+        }
+    }
+
+    detach(world, entity);
+
+    match parent {
+        Some(parent) => {
+            let _ = world.insert_one(entity, Parent(parent));
+
+            let added = match world.get::<&mut Children>(parent) {
+                Ok(mut children) => {
+                    children.0.push(entity);
+                    true
+                }
+                Err(_) => false,
+            };
+
+            if !added {
+                let _ = world.insert_one(parent, Children(vec![entity]));
+            }
+
+            update_transform(world, entity, parent_world_matrix(world, parent));
+        }
+        None => update_transform(world, entity, ultraviolet::Mat4::identity()),
+    }
+
+    Ok(())
+}
+
+/// Like `set_parent`, but the local transform is adjusted so the entity stays where it is in the world.
+pub fn set_parent_keep_world(
+    world: &mut hecs::World,
+    entity: Entity,
+    parent: Option<Entity>,
+) -> anyhow::Result<()> {
+    let world_matrix = world
+        .get::<&Transform>(entity)
+        .ok()
+        .map(|transform| transform.world_matrix());
+
+    set_parent(world, entity, parent)?;
+
+    if let (Some(world_matrix), Ok(mut transform)) = (world_matrix, world.get::<&mut Transform>(entity)) {
+        let local = transform.parent_matrix().inversed() * world_matrix;
+        transform.set_local_matrix(&local);
+    }
+
+    Ok(())
+}
+
+/// Adds a child to the entity, see `set_parent`.
+pub fn add_child(world: &mut hecs::World, parent: Entity, child: Entity) -> anyhow::Result<()> {
+    set_parent(world, child, Some(parent))
+}
+
+/// Despawns the entity and all of its descendants, and removes it from its parent.
+pub fn despawn_recursive(world: &mut hecs::World, entity: Entity) -> anyhow::Result<()> {
+    if !world.contains(entity) {
+        return Err(anyhow::anyhow!("Entity {:?} doesn't exist.", entity));
+    }
+
+    detach(world, entity);
+
+    let mut pending = vec![entity];
+    while let Some(entity) = pending.pop() {
+        pending.extend(children(world, entity));
+        let _ = world.despawn(entity);
+    }
+
+    Ok(())
+}
+
+/// Removes the entity from its parent's children, if it has a parent.
+fn detach(world: &mut hecs::World, entity: Entity) {
+    let Ok(Parent(parent)) = world.remove_one::<Parent>(entity) else {
+        return;
+    };
+
+    let now_empty = match world.get::<&mut Children>(parent) {
+        Ok(mut children) => {
+            children.0.retain(|child| *child != entity);
+            children.0.is_empty()
+        }
+        Err(_) => false,
+    };
+
+    if now_empty {
+        let _ = world.remove_one::<Children>(parent);
+    }
+}
+
+/// World matrix of an entity, the identity if it has no transform.
+fn parent_world_matrix(world: &hecs::World, entity: Entity) -> ultraviolet::Mat4 {
+    world
+        .get::<&Transform>(entity)
+        .map(|transform| transform.world_matrix())
+        .unwrap_or(ultraviolet::Mat4::identity())
+}
+
+fn update_transform(world: &hecs::World, entity: Entity, parent_matrix: ultraviolet::Mat4) {
+    if let Ok(mut transform) = world.get::<&mut Transform>(entity) {
+        transform.set_parent_matrix(parent_matrix);
+    }
+}
+
+/// Applies the world matrix of every parent to its children.
+/// Runs once per frame before physics and rendering, so changes made by systems show up the same frame.
+/// Scenes that were simulated are propagated again after physics, so moved bodies carry their children.
+pub(crate) fn propagate_transforms(world: &mut hecs::World) {
+    let roots: Vec<Entity> = world
+        .query::<(&Children, Option<&Parent>)>()
+        .iter()
+        .filter(|(_, (_, parent))| parent.is_none())
+        .map(|(entity, _)| entity)
+        .collect();
+
+    let mut pending: Vec<(Entity, ultraviolet::Mat4)> = roots
+        .into_iter()
+        .map(|root| (root, parent_world_matrix(world, root)))
+        .collect();
+
+    while let Some((parent, parent_matrix)) = pending.pop() {
+        for child in children(world, parent) {
+            update_transform(world, child, parent_matrix);
+
+            // Entities without a transform pass their parent's on to their children
+            let child_matrix = world
+                .get::<&Transform>(child)
+                .map(|transform| transform.world_matrix())
+                .unwrap_or(parent_matrix);
+
+            pending.push((child, child_matrix));
+        }
+    }
+}
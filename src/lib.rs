@@ -1,13 +1,21 @@
 pub mod assets;
 pub mod camera;
+pub mod commands;
+pub mod events;
+pub mod hierarchy;
 pub mod input;
 pub mod physics;
 pub mod renderer;
 pub mod resources;
 pub mod scene;
+pub mod shader;
+pub mod state;
 pub mod system;
 pub mod texture;
+pub mod time;
+pub mod timer;
 pub mod transform;
+pub mod tween;
 pub mod window;
 
 #[cfg(feature = "renderer_2d")]
@@ -30,20 +38,52 @@ pub use physics_3d::Physics3D;
 
 pub(crate) mod uniform;
 
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    rc::Rc,
+};
+
+use ahash::AHashMap;
+
 pub struct App {
     pub window: window::Window,
     pub renderer: Box<dyn renderer::Renderer>,
     pub input: input::Input,
     pub resources: resources::ResourceManager,
+    /// Spawns, despawns and resources queued by systems
+    pub commands: commands::Commands,
+    /// Adds, removes and pauses systems while the app runs
+    pub systems: system::SystemRegistry,
+    /// Game defined events sent between systems
+    pub events: events::Events,
     pub assets: assets::AssetManager,
     pub scenes: scene::ScenesManager,
     pub physics: Option<Box<dyn physics::Physics>>,
     pub perf_stats: PerformanceStatistics,
+    /// Set by `veto_close` while the close request callbacks run
+    close_vetoed: bool,
+    exit_requested: bool,
+}
+
+impl App {
+    /// Keeps the window open, call this from a `CloseRequested` callback.
+    /// Use `exit` to close the app later, e.g. once the game is saved.
+    pub fn veto_close(&mut self) {
+        self.close_vetoed = true;
+    }
+
+    /// Closes the app after the current frame. `ShutDown` callbacks still run.
+    pub fn exit(&mut self) {
+        self.exit_requested = true;
+    }
 }
 
 pub struct AppBuilder {
     app: Option<App>,
     systems: Vec<system::System>,
+    /// `Rc<RefCell<state::StateSystems<S>>>` for every state type added with `add_state`
+    states: AHashMap<TypeId, Box<dyn Any>>,
     renderer: Option<Box<dyn renderer::Renderer>>,
     physics: Option<Box<dyn physics::Physics>>,
 }
@@ -61,74 +101,38 @@ impl AppBuilder {
         AppBuilder {
             app: None,
             systems: Vec::new(),
+            states: AHashMap::new(),
             renderer: None,
             physics: None,
         }
     }
 
-    fn run_event_system(&mut self, event: crate::system::EventCallbackType, app: &mut App) {
-        // Iterate over all systems
-        // Find a system where the system_type is Event(event)
-        // Run that system
-
-        for system in &mut self.systems {
-            if let system::SystemType::EventCallback(e) = &system.system_type {
-                // If the system is registered to a scene, check if the current scene is the same as the system's scene
-                // If not, continue
-                let mut run_system = true;
-                if let Some(scene) = app.scenes.current_scene_name() {
-                    if let Some(current_scene) = &system.scene {
-                        if current_scene != scene {
-                            run_system = false;
-                        }
-                    }
-                }
-
-                if *e == event {
-                    if run_system {
-                        (system.update)(app, &system.last_run.elapsed());
-                    }
-
-                    // Update delta time
-                    system.last_run = std::time::Instant::now();
-                }
-            }
-        }
-    }
-
     pub fn run(mut self) -> anyhow::Result<()> {
         log::info!("Cobalt v{}", env!("CARGO_PKG_VERSION"));
         log::info!("Starting...");
 
+        // Ordering mistakes are reported before the window opens
+        let mut schedule = system::schedule::Schedule::new(std::mem::take(&mut self.systems))?;
+
         let event_loop = self.build()?;
 
         let mut app = self.app.take().expect("App not initialized.");
 
-        // The scene manager takes a mutable pointer to the app to pass it along to the scene generator.
-        // Read more in the scene module.
-        // TODO: Find a better way to do this.
-        app.scenes.app_ref = &mut app as *mut App;
-
         // Initialize the renderer
         app.renderer.as_mut().initialize(&app.window);
 
+        schedule.track(&mut app.systems);
+
         // Reset the last_run time for all systems
-        for system in &mut self.systems {
-            system.last_run = std::time::Instant::now();
-        }
+        schedule.reset_timers();
 
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
         // Run all the startup systems
-        for system in &mut self.systems {
-            if let system::SystemType::Startup = system.system_type {
-                (system.update)(&mut app, &system.last_run.elapsed());
-            }
-        }
+        schedule.run_startup(&mut app);
 
-        // Remove all the startup systems
-        self.systems
-            .retain(|s| !matches!(s.system_type, system::SystemType::Startup));
+        // A size of zero means the window is minimized
+        let mut minimized = false;
 
         // Run the loop
         event_loop.run(|event, elwt| {
@@ -138,125 +142,197 @@ impl AppBuilder {
                 Event::WindowEvent { event, .. } => {
                     match event {
                         WindowEvent::CloseRequested => {
-                            // Cleanup
-                            self.run_event_system(system::EventCallbackType::ShutDown, &mut app);
-
-                            elwt.exit();
+                            // Callbacks can keep the window open, e.g. to ask to save first
+                            app.close_vetoed = false;
+                            schedule.run_event(system::EventCallbackType::CloseRequested, &mut app);
 
-                            // Drop all assets
-                            app.assets.drop_all();
+                            if app.close_vetoed {
+                                log::info!("Close request vetoed.");
+                            } else {
+                                elwt.exit();
+                            }
                         }
                         WindowEvent::RedrawRequested => {
+                            // Events from two frames ago are dropped
+                            app.events.update();
+
+                            let (delta, real_delta) = time::update(&mut app);
+
+                            schedule.run_event(system::EventCallbackType::FrameBegin, &mut app);
+
+                            // Finish background loads and switch to scenes that were waiting on them
+                            app.assets.update_loads();
+                            app.assets.update_hot_reload();
+
+                            if let Some(name) = app.scenes.take_ready_scene(&app.assets) {
+                                if let Err(e) = app.scenes.load(name) {
+                                    log::error!("Failed to load scene: {}", e);
+                                }
+                            }
+
+                            // Switches scenes once a transition covers the screen
+                            app.scenes.update_transition();
+                            commands::apply(&mut app);
+                            schedule.dispatch_scene_events(&mut app);
+
+                            // Timers finish before systems run, so they see `just_finished` this frame
+                            timer::update_timers(&mut app, delta, real_delta);
+
                             // Update and run systems
-                            for system in &mut self.systems {
-                                // Check if the system is registered to a scene
-                                let mut run_system = true;
-                                if let Some(scene) = &system.scene {
-                                    // Check if the current scene is the same as the system's scene
-                                    if let Some(current_scene) = app.scenes.current_scene_name() {
-                                        if current_scene != scene {
-                                            run_system = false;
-                                        }
-                                    }
-                                    // If the current scene is None, continue
-                                    else {
-                                        run_system = false;
+                            schedule.run_stage(system::Stage::PreUpdate, &mut app);
+                            schedule.run_stage(system::Stage::Update, &mut app);
+                            schedule.run_stage(system::Stage::PostUpdate, &mut app);
+
+                            tween::update_tweens(&mut app, delta, real_delta);
+
+                            // Apply parent transforms before physics and rendering
+                            for scene in app.scenes.loaded_scenes_mut() {
+                                hierarchy::propagate_transforms(&mut scene.world);
+                            }
+
+                            // Physics follows game time, so it stops while paused.
+                            // Additive scenes keep running, so their bodies move too
+                            if !delta.is_zero() {
+                                if let Some(physics) = &mut app.physics {
+                                    for (_, scene) in app.scenes.active_scenes_mut() {
+                                        physics.simulate(&mut scene.world, delta);
                                     }
                                 }
+                            }
 
-                                match system.system_type {
-                                    system::SystemType::Timed(duration) => {
-                                        if system.last_run.elapsed() >= duration {
-                                            // Still loop and check to track last_run times
-                                            if run_system {
-                                                (system.update)(
-                                                    &mut app,
-                                                    &system.last_run.elapsed(),
-                                                );
-                                            }
-                                            system.last_run = std::time::Instant::now();
-                                        }
-                                    }
-                                    system::SystemType::Update => {
-                                        // Still loop and check to track last_run times
-                                        if run_system {
-                                            (system.update)(&mut app, &system.last_run.elapsed());
-                                        }
-                                        system.last_run = std::time::Instant::now();
-                                    }
-                                    _ => {}
+                            // Bodies moved by physics carry their children along in the frame that is drawn
+                            if app.physics.is_some() && !delta.is_zero() {
+                                for (_, scene) in app.scenes.active_scenes_mut() {
+                                    hierarchy::propagate_transforms(&mut scene.world);
                                 }
                             }
 
                             app.assets.update_ref_counts();
 
-                            // Update camera buffer
-                            if let Some(scene) = app.scenes.current_scene_mut() {
+                            schedule.run_stage(system::Stage::PreRender, &mut app);
+
+                            if app.scenes.current_scene().is_none() {
+                                log::error!("No scene loaded!");
+                            }
+
+                            // Update camera buffers
+                            for scene in app.scenes.loaded_scenes_mut() {
                                 if let Some(camera) = &mut scene.camera {
                                     camera.update_uniform(&app.window);
+                                }
+                            }
 
-                                    // Render
-                                    let res = app.renderer.render(
-                                        &mut app.window,
-                                        camera,
-                                        &mut scene.world,
-                                    );
+                            let overlay = app.scenes.transition_overlay();
 
-                                    if let Err(e) = res {
-                                        log::error!("Failed to render: {}", e);
+                            // Paused scenes are drawn first, then the current scene, then additive scenes
+                            let mut layers: Vec<renderer::SceneLayer> = app
+                                .scenes
+                                .loaded_scenes_mut()
+                                .filter_map(|scene| {
+                                    let scene::Scene { world, camera } = scene;
+
+                                    match camera {
+                                        Some(camera) => Some(renderer::SceneLayer { camera, world }),
+                                        None => {
+                                            log::error!("No camera in scene!");
+                                            None
+                                        }
                                     }
-                                } else {
-                                    log::error!("No camera in scene!");
-                                }
-                            } else {
-                                log::error!("No scene loaded!");
+                                })
+                                .collect();
+
+                            // Render
+                            let res = app.renderer.render(&mut app.window, &mut layers, overlay);
+
+                            if let Err(e) = res {
+                                log::error!("Failed to render: {}", e);
                             }
 
+                            schedule.run_stage(system::Stage::PostRender, &mut app);
+
+                            schedule.run_event(system::EventCallbackType::FrameEnd, &mut app);
+
                             app.perf_stats.tick();
                         }
                         WindowEvent::Resized(s) => {
-                            let res = app.window.resize(s);
+                            if s.width == 0 || s.height == 0 {
+                                // The surface can't be resized to zero, keep the old size until restored
+                                if !minimized {
+                                    minimized = true;
+                                    schedule.run_event(system::EventCallbackType::Minimized, &mut app);
+                                }
+                            } else {
+                                if minimized {
+                                    minimized = false;
+                                    schedule.run_event(system::EventCallbackType::Restored, &mut app);
+                                }
 
-                            // Event: WindowResize
-                            self.run_event_system(
-                                system::EventCallbackType::WindowResize,
-                                &mut app,
-                            );
+                                let res = app.window.resize(s);
 
-                            if let Err(e) = res {
-                                log::error!("Failed to resize window: {}", e);
-                            }
+                                // Event: WindowResize
+                                schedule.run_event(system::EventCallbackType::WindowResize, &mut app);
 
-                            app.renderer.resize_callback(&app.window);
+                                if let Err(e) = res {
+                                    log::error!("Failed to resize window: {}", e);
+                                }
+
+                                app.renderer.resize_callback(&app.window);
+                            }
                         }
-                        WindowEvent::ScaleFactorChanged { .. } => {
+                        WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
                             let res = app.window.resize(app.window.winit_win.inner_size());
 
+                            app.events.send(events::ScaleFactorChanged { scale_factor });
+                            schedule.run_event(system::EventCallbackType::ScaleFactorChanged, &mut app);
+
                             // Event: WindowResize
-                            self.run_event_system(
-                                system::EventCallbackType::WindowResize,
-                                &mut app,
-                            );
+                            schedule.run_event(system::EventCallbackType::WindowResize, &mut app);
 
                             if let Err(e) = res {
                                 log::error!("Failed to resize window: {}", e);
                             }
                         }
+                        WindowEvent::Focused(focused) => {
+                            let event = if focused {
+                                system::EventCallbackType::FocusGained
+                            } else {
+                                system::EventCallbackType::FocusLost
+                            };
+
+                            schedule.run_event(event, &mut app);
+                        }
+                        WindowEvent::Moved(position) => {
+                            app.events.send(events::WindowMoved {
+                                x: position.x,
+                                y: position.y,
+                            });
+                            schedule.run_event(system::EventCallbackType::Moved, &mut app);
+                        }
+                        WindowEvent::DroppedFile(ref path) => {
+                            app.events.send(events::FileDropped { path: path.clone() });
+                            schedule.run_event(system::EventCallbackType::FileDropped, &mut app);
+                        }
                         _ => {}
                     }
 
                     app.input.update(&event).expect("Failed to update input");
                 }
                 Event::AboutToWait => {
+                    if app.exit_requested {
+                        elwt.exit();
+                    }
+
                     app.window.winit_win.request_redraw();
                 }
-                _ => {}
-            }
+                Event::LoopExiting => {
+                    // Cleanup, runs however the loop ends
+                    schedule.run_event(system::EventCallbackType::ShutDown, &mut app);
+                    scene::ScenesManager::unload_all(&mut app);
 
-            if let Some(physics) = &mut app.physics {
-                if let Some(scene) = app.scenes.current_scene_mut() {
-                    physics.simulate(&mut scene.world);
+                    // Drop all assets
+                    app.assets.drop_all();
                 }
+                _ => {}
             }
         })?;
 
@@ -274,11 +350,19 @@ impl AppBuilder {
             return Err(anyhow::anyhow!("No renderer specified."));
         }
 
+        let assets = assets::AssetManager::new(&window);
+
+        let mut resources = resources::ResourceManager::new();
+        resources.create_resource(time::Time::new())?;
+
         self.app = Some(App {
             window,
             renderer: self.renderer.take().unwrap(),
-            resources: resources::ResourceManager::new(),
-            assets: assets::AssetManager::new(),
+            resources,
+            commands: commands::Commands::new(),
+            systems: system::SystemRegistry::new(),
+            events: events::Events::new(),
+            assets,
             scenes: scene::ScenesManager::new(),
             input: input::Input::new(),
             physics: if let Some(physics) = self.physics.take() {
@@ -287,6 +371,8 @@ impl AppBuilder {
                 None
             },
             perf_stats: PerformanceStatistics::new(std::time::Duration::from_millis(500)),
+            close_vetoed: false,
+            exit_requested: false,
         });
 
         Ok(event_loop)
@@ -295,12 +381,55 @@ impl AppBuilder {
     /// Registers a system with the app.
     /// Scene is an optional parameter that specifies the scene the system should be registered to.
     /// If no scene is specified, the system will be registered to all scenes.
+    /// Systems run in registration order, unless they are ordered with `System::before` and `System::after`.
     pub fn register_system(&mut self, system: system::System) {
         if !self.systems.iter().any(|s| s.uuid == system.uuid) {
             self.systems.push(system);
         }
     }
 
+    /// Adds an app state of type `S`, usually an enum, stored as the resource `state::State<S>`.
+    /// Its transitions are applied at the start of `Stage::PreUpdate`, before any other system in that stage.
+    pub fn add_state<S: state::StateType>(&mut self, initial: S) {
+        if self.states.contains_key(&TypeId::of::<S>()) {
+            log::error!("State {} was already added.", std::any::type_name::<S>());
+            return;
+        }
+
+        let systems = Rc::new(RefCell::new(state::StateSystems::<S>::new()));
+        self.states.insert(TypeId::of::<S>(), Box::new(systems.clone()));
+
+        // At the front, so the state exists for the startup systems and changes apply before other systems
+        self.systems.insert(0, state::startup_system(initial));
+        self.systems.insert(1, state::transition_system(systems));
+    }
+
+    /// Runs the system every time the state is entered, including the initial state on the first frame.
+    pub fn on_enter<S: state::StateType>(&mut self, state: S, system: system::System) {
+        match self.state_systems::<S>() {
+            Some(systems) => systems.borrow_mut().add_on_enter(state, system),
+            None => log::error!("Add the state {} before its systems.", std::any::type_name::<S>()),
+        }
+    }
+
+    /// Runs the system every time the state is left.
+    pub fn on_exit<S: state::StateType>(&mut self, state: S, system: system::System) {
+        match self.state_systems::<S>() {
+            Some(systems) => systems.borrow_mut().add_on_exit(state, system),
+            None => log::error!("Add the state {} before its systems.", std::any::type_name::<S>()),
+        }
+    }
+
+    /// Registers the system to only run while in the state.
+    /// Its scene filter still applies, so it can be bound to a state and a scene at once.
+    pub fn on_update<S: state::StateType>(&mut self, state: S, system: system::System) {
+        self.register_system(system.run_if(system::RunCondition::in_state(state)));
+    }
+
+    fn state_systems<S: state::StateType>(&self) -> Option<&Rc<RefCell<state::StateSystems<S>>>> {
+        self.states.get(&TypeId::of::<S>())?.downcast_ref()
+    }
+
     pub fn with_renderer(mut self, renderer: Box<dyn renderer::Renderer>) -> Self {
         self.renderer = Some(renderer);
 
@@ -1,7 +1,6 @@
-
-
 pub trait Physics {
-    /// Run as fast as possible.
+    /// Runs once per frame with the scaled frame time from `time::Time`,
+    /// it isn't called while the game is paused.
     /// The check for the time step is done in the physics system.
-    fn simulate(&mut self, world: &mut hecs::World);
-}
\ No newline at end of file
+    fn simulate(&mut self, world: &mut hecs::World, delta: std::time::Duration);
+}
@@ -18,8 +18,6 @@ pub struct Physics2D {
 
     // World variables
     pub constants: PhysicsConstants,
-
-    last_sim: std::time::Instant,
 }
 
 impl Physics2D {
@@ -29,17 +27,12 @@ impl Physics2D {
             constants: PhysicsConstants {
                 gravity: Vec2::new(0.0, -9.81),
             },
-            last_sim: std::time::Instant::now(),
         }
     }
 }
 
 impl Physics for Physics2D {
-    fn simulate(&mut self, world: &mut hecs::World) {
-        let now = std::time::Instant::now();
-        let delta = now - self.last_sim;
-        self.last_sim = now;
-
+    fn simulate(&mut self, world: &mut hecs::World, delta: std::time::Duration) {
         let delta = delta.as_secs_f32();
 
         for (entity, (transform, rigidbody)) in world
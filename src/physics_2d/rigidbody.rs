@@ -1,5 +1,6 @@
 use std::time::Instant;
 
+use serde::{Deserialize, Serialize};
 use ultraviolet::Vec2;
 
 use crate::transform::Transform;
@@ -20,6 +21,7 @@ struct Force2D {
     pub mode: ForceMode2D,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Rigidbody2D {
     pub enabled: bool,
 
@@ -28,6 +30,8 @@ pub struct Rigidbody2D {
     pub mass: f32,
     pub friction: f32,
 
+    /// Pending forces are not saved with scenes
+    #[serde(skip)]
     forces: Vec<Force2D>,
 
     pub constrain_x: bool,
@@ -1,12 +1,39 @@
-use crate::{window::Window, camera::Camera};
+use crate::{assets::Asset, camera::Camera, shader::Shader, window::Window};
+
+/// A scene to draw, with the camera it is seen through.
+pub struct SceneLayer<'a> {
+    pub camera: &'a Camera,
+    pub world: &'a mut hecs::World,
+}
+
+/// A colored rectangle drawn over every layer, e.g. for scene transitions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenOverlay {
+    /// RGBA
+    pub color: [f32; 4],
+    /// Left, top, right, bottom from 0 to 1, with the origin at the top left of the window
+    pub rect: [f32; 4],
+}
 
 pub trait Renderer {
-    /// Updates all renderables then renders them to one render pass
-    fn render(&mut self, window: &mut Window, camera: &Camera, world: &mut hecs::World) -> anyhow::Result<()>;
+    /// Updates all renderables then renders the layers in order, each on top of the previous one,
+    /// followed by the overlay
+    fn render(
+        &mut self,
+        window: &mut Window,
+        layers: &mut [SceneLayer],
+        overlay: Option<ScreenOverlay>,
+    ) -> anyhow::Result<()>;
 
     /// Called when the window is resized
     fn resize_callback(&mut self, window: &Window);
 
     /// Called before the first frame
     fn initialize(&mut self, window: &Window);
-}
\ No newline at end of file
+
+    /// Draws a renderable type, e.g. `TypeId::of::<Sprite>()`, with a different shader.
+    /// The pipeline is rebuilt whenever the shader asset is hot reloaded.
+    fn set_shader(&mut self, _renderable: std::any::TypeId, _shader: Asset<Shader>) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!("This renderer doesn't support custom shaders."))
+    }
+}
@@ -0,0 +1,253 @@
+use ahash::AHashMap;
+
+use crate::{assets::Asset, texture::Texture};
+
+/// A single glyph in a bitmap font, all values are in atlas pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct BitmapGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub x_advance: f32,
+    pub page: usize,
+}
+
+/// A pre-baked bitmap font in the AngelCode BMFont text format.
+/// Glyphs are drawn straight from the atlas pages, so they stay pixel exact.
+pub struct BitmapFont {
+    line_height: f32,
+    base: f32,
+    scale_w: f32,
+    scale_h: f32,
+    glyphs: AHashMap<char, BitmapGlyph>,
+    kernings: AHashMap<(char, char), f32>,
+    pub(crate) pages: Vec<Asset<Texture>>,
+}
+
+impl BitmapFont {
+    /// Loads a font from the contents of a BMFont `.fnt` file in the text format.
+    /// The atlas pages must be given in the order of their page ids.
+    /// Use `BitmapFont::page_files` to find which images the file refers to.
+    pub fn load(fnt: &str, pages: Vec<Asset<Texture>>) -> anyhow::Result<Self> {
+        let mut line_height = None;
+        let mut base = 0.0;
+        let mut scale = None;
+        let mut page_count = None;
+        let mut glyphs = AHashMap::new();
+        let mut kernings = AHashMap::new();
+
+        for (line_number, line) in fnt.lines().enumerate() {
+            let (tag, attributes) = parse_line(line);
+
+            match tag {
+                "common" => {
+                    line_height = Some(number(&attributes, line_number, "lineHeight")? as f32);
+                    base = number(&attributes, line_number, "base")? as f32;
+                    scale = Some((
+                        number(&attributes, line_number, "scaleW")? as f32,
+                        number(&attributes, line_number, "scaleH")? as f32,
+                    ));
+                    page_count = Some(number(&attributes, line_number, "pages")? as usize);
+                }
+                "char" => {
+                    let id = number(&attributes, line_number, "id")?;
+                    let c = char::from_u32(id as u32).ok_or(anyhow::anyhow!(
+                        "Invalid char id {} on line {} of bitmap font.",
+                        id,
+                        line_number + 1
+                    ))?;
+
+                    glyphs.insert(
+                        c,
+                        BitmapGlyph {
+                            x: number(&attributes, line_number, "x")? as u32,
+                            y: number(&attributes, line_number, "y")? as u32,
+                            width: number(&attributes, line_number, "width")? as u32,
+                            height: number(&attributes, line_number, "height")? as u32,
+                            x_offset: number(&attributes, line_number, "xoffset")? as f32,
+                            y_offset: number(&attributes, line_number, "yoffset")? as f32,
+                            x_advance: number(&attributes, line_number, "xadvance")? as f32,
+                            page: number(&attributes, line_number, "page")? as usize,
+                        },
+                    );
+                }
+                "kerning" => {
+                    let first = char::from_u32(number(&attributes, line_number, "first")? as u32);
+                    let second = char::from_u32(number(&attributes, line_number, "second")? as u32);
+
+                    if let (Some(first), Some(second)) = (first, second) {
+                        let amount = number(&attributes, line_number, "amount")?;
+                        kernings.insert((first, second), amount as f32);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let line_height = line_height.ok_or(anyhow::anyhow!(
+            "Bitmap font is missing the 'common' line, only the text format is supported."
+        ))?;
+        let (scale_w, scale_h) = scale.unwrap();
+        let page_count = page_count.unwrap();
+
+        if pages.len() != page_count {
+            return Err(anyhow::anyhow!(
+                "Bitmap font has {} pages but {} textures were given.",
+                page_count,
+                pages.len()
+            ));
+        }
+
+        if let Some(glyph) = glyphs.values().find(|g| g.page >= page_count) {
+            return Err(anyhow::anyhow!(
+                "Bitmap font glyph refers to page {} which doesn't exist.",
+                glyph.page
+            ));
+        }
+
+        Ok(Self {
+            line_height,
+            base,
+            scale_w,
+            scale_h,
+            glyphs,
+            kernings,
+            pages,
+        })
+    }
+
+    /// Returns the atlas file names listed in a `.fnt` file, ordered by page id.
+    pub fn page_files(fnt: &str) -> Vec<String> {
+        let mut files = Vec::new();
+
+        for line in fnt.lines() {
+            let (tag, attributes) = parse_line(line);
+
+            if tag != "page" {
+                continue;
+            }
+
+            let id = attributes
+                .iter()
+                .find(|(k, _)| *k == "id")
+                .and_then(|(_, v)| v.parse::<usize>().ok());
+            let file = attributes.iter().find(|(k, _)| *k == "file");
+
+            if let (Some(id), Some((_, file))) = (id, file) {
+                if files.len() <= id {
+                    files.resize(id + 1, String::new());
+                }
+                files[id] = file.to_string();
+            }
+        }
+
+        files
+    }
+
+    /// Distance between two lines in font pixels.
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Distance from the top of a line to the baseline in font pixels.
+    pub fn base(&self) -> f32 {
+        self.base
+    }
+
+    /// Size of the atlas pages in pixels.
+    pub fn atlas_size(&self) -> (f32, f32) {
+        (self.scale_w, self.scale_h)
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&BitmapGlyph> {
+        self.glyphs.get(&c)
+    }
+
+    /// Extra horizontal advance between two characters in font pixels.
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+
+    /// Width of a single line of text in font pixels.
+    pub fn line_width(&self, line: &str) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+
+        for c in line.chars() {
+            if let Some(glyph) = self.glyph(c) {
+                if let Some(previous) = previous {
+                    width += self.kerning(previous, c);
+                }
+                width += glyph.x_advance;
+            }
+            previous = Some(c);
+        }
+
+        width
+    }
+
+    /// Size of a block of text in font pixels, lines are split on '\n'.
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let width = text
+            .split('\n')
+            .map(|line| self.line_width(line))
+            .fold(0.0, f32::max);
+
+        let height = text.split('\n').count() as f32 * self.line_height;
+
+        (width, height)
+    }
+}
+
+fn number(attributes: &[(&str, &str)], line_number: usize, key: &str) -> anyhow::Result<i64> {
+    let value = attributes
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+        .ok_or(anyhow::anyhow!(
+            "Missing '{}' on line {} of bitmap font.",
+            key,
+            line_number + 1
+        ))?;
+
+    value.parse::<i64>().map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid '{}' on line {} of bitmap font.",
+            key,
+            line_number + 1
+        )
+    })
+}
+
+/// Splits a BMFont line into its tag and key=value pairs.
+/// Values may be quoted and contain spaces.
+fn parse_line(line: &str) -> (&str, Vec<(&str, &str)>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+    let mut attributes = Vec::new();
+
+    loop {
+        rest = rest.trim_start();
+
+        let Some((key, after_key)) = rest.split_once('=') else {
+            break;
+        };
+
+        let (value, after_value) = if let Some(quoted) = after_key.strip_prefix('"') {
+            quoted.split_once('"').unwrap_or((quoted, ""))
+        } else {
+            after_key
+                .split_once(char::is_whitespace)
+                .unwrap_or((after_key, ""))
+        };
+
+        attributes.push((key.trim(), value));
+        rest = after_value;
+    }
+
+    (tag, attributes)
+}
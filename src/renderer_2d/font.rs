@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use crate::assets::{AssetError, AssetLoader, LoadContext};
+
+/// A font loaded from TTF/OTF bytes.
+/// Store it as an asset and pass it to a `Text` or `TextSpan` to use it.
+/// The font is registered with the renderer's font system the first time it is used.
+pub struct Font {
+    pub(crate) id: uuid::Uuid,
+    pub(crate) data: Arc<Vec<u8>>,
+    family: String,
+}
+
+impl Font {
+    /// Loads a font from the raw bytes of a TTF/OTF file.
+    /// Fails if the bytes do not contain a valid font face.
+    pub fn load(bytes: &[u8]) -> anyhow::Result<Self> {
+        // Parse the data once with a throwaway database to find the family name,
+        // which is what text attributes use to select the font later on.
+        let mut db = glyphon::fontdb::Database::new();
+        db.load_font_data(bytes.to_vec());
+
+        let family = db
+            .faces()
+            .next()
+            .and_then(|face| face.families.first())
+            .map(|(name, _)| name.clone())
+            .ok_or(anyhow::anyhow!("No valid font face found in font data."))?;
+
+        Ok(Self {
+            // Derived from the data, so the same font loaded twice is only registered once
+            id: uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, bytes),
+            data: Arc::new(bytes.to_vec()),
+            family,
+        })
+    }
+
+    /// The family name of the font, as stored in the font file.
+    pub fn family(&self) -> &str {
+        self.family.as_str()
+    }
+
+    /// Registers the font with the font system if it hasn't been already.
+    pub(crate) fn ensure_loaded(
+        &self,
+        font_system: &mut glyphon::FontSystem,
+        loaded_fonts: &mut ahash::AHashSet<uuid::Uuid>,
+    ) {
+        if loaded_fonts.insert(self.id) {
+            font_system
+                .db_mut()
+                .load_font_source(glyphon::fontdb::Source::Binary(self.data.clone()));
+        }
+    }
+}
+
+/// Loads TTF/OTF files with `AssetManager::load`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FontLoader;
+
+impl AssetLoader for FontLoader {
+    type Asset = Font;
+    type Decoded = Font;
+
+    fn decode(&self, bytes: &[u8], context: &LoadContext) -> Result<Font, AssetError> {
+        Font::load(bytes).map_err(|e| context.decode_error(e))
+    }
+
+    /// Fonts don't need the GPU, everything happens in `decode`.
+    fn create(&self, font: Font, _context: &LoadContext) -> Result<Font, AssetError> {
+        Ok(font)
+    }
+}
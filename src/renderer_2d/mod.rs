@@ -1,19 +1,39 @@
+pub mod bitmap_font;
+pub mod font;
+mod overlay;
 pub mod renderables;
 
-use ahash::AHashMap;
+use std::any::TypeId;
 
-use crate::{camera::Camera, renderer::Renderer, transform::Transform, window::Window};
+use ahash::{AHashMap, AHashSet};
+use pollster::FutureExt;
 
-use self::renderables::{sprite::Sprite, Renderable, TranslucentSprite, Text, Rect};
+use crate::{
+    assets::Asset,
+    renderer::{Renderer, SceneLayer, ScreenOverlay},
+    shader::Shader,
+    transform::Transform,
+    window::Window,
+};
+
+use self::overlay::OverlayRenderer;
+use self::renderables::{sprite::Sprite, BitmapText, NineSlice, Renderable, TranslucentSprite, Text, Rect};
+
+type CreatePipeline = fn(&mut Window, Option<&wgpu::ShaderModule>) -> anyhow::Result<wgpu::RenderPipeline>;
 
 pub struct Renderer2D {
     pipelines: AHashMap<std::any::TypeId, wgpu::RenderPipeline>,
+    /// Shaders set with `Renderer::set_shader`, by the type of the pipeline
+    shaders: AHashMap<TypeId, Asset<Shader>>,
     depth_texture: Option<wgpu::Texture>,
+    /// Created the first time an overlay is drawn
+    overlay: Option<OverlayRenderer>,
 
     pub(crate) font_system: glyphon::FontSystem,
     pub(crate) swash_cache: glyphon::SwashCache,
+    /// Ids of the `Font` assets that have been added to the font system
+    pub(crate) loaded_fonts: AHashSet<uuid::Uuid>,
     pub(crate) text_atlas: Option<glyphon::TextAtlas>,
-    pub(crate) text_renderer: Option<glyphon::TextRenderer>,
 }
 
 impl Renderer2D {
@@ -22,11 +42,13 @@ impl Renderer2D {
     pub fn new() -> Renderer2D {
         Renderer2D {
             pipelines: AHashMap::new(),
+            shaders: AHashMap::new(),
             depth_texture: None,
+            overlay: None,
             font_system: glyphon::FontSystem::new(),
             swash_cache: glyphon::SwashCache::new(),
+            loaded_fonts: AHashSet::new(),
             text_atlas: None,
-            text_renderer: None,
         }
     }
 
@@ -50,6 +72,168 @@ impl Renderer2D {
 
         self.depth_texture = Some(window.device.create_texture(&desc));
     }
+
+    fn create_pipelines(&mut self, window: &mut Window) -> anyhow::Result<()> {
+        // Rebuild the pipelines whose shader was hot reloaded
+        for (type_id, shader) in self.shaders.iter_mut() {
+            if shader.refresh() {
+                self.pipelines.remove(type_id);
+            }
+        }
+
+        // TODO: Make a macro that does this for a bunch of types automatically
+        if !self.pipelines.contains_key(&Sprite::type_id()) {
+            // Generate pipeline
+            let pipeline = self.build_pipeline(window, Sprite::type_id(), Sprite::create_pipeline)?;
+
+            self.pipelines
+                .extend(std::iter::once((Sprite::type_id(), pipeline)));
+        }
+
+        if !self.pipelines.contains_key(&TranslucentSprite::type_id()) {
+            // Generate pipeline
+            let pipeline = self.build_pipeline(
+                window,
+                TranslucentSprite::type_id(),
+                TranslucentSprite::create_pipeline,
+            )?;
+
+            self.pipelines
+                .extend(std::iter::once((TranslucentSprite::type_id(), pipeline)));
+        }
+
+        if !self.pipelines.contains_key(&Text::type_id()) {
+            // Generate pipeline
+            let pipeline = self.build_pipeline(window, Text::type_id(), Text::create_pipeline)?;
+
+            self.pipelines
+                .extend(std::iter::once((Text::type_id(), pipeline)));
+        }
+
+        if !self.pipelines.contains_key(&Rect::type_id()) {
+            // Generate pipeline
+            let pipeline = self.build_pipeline(window, Rect::type_id(), Rect::create_pipeline)?;
+
+            self.pipelines
+                .extend(std::iter::once((Rect::type_id(), pipeline)));
+        }
+
+        Ok(())
+    }
+
+    /// Creates a pipeline with the shader set for the type, or the built-in one.
+    /// A shader that doesn't fit the pipeline's bindings is logged and the built-in one is used instead.
+    fn build_pipeline(
+        &self,
+        window: &mut Window,
+        type_id: TypeId,
+        create: CreatePipeline,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let Some(shader) = self.shaders.get(&type_id) else {
+            return create(window, None);
+        };
+
+        window.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create(window, Some(shader.module()));
+
+        if let Some(e) = window.device.pop_error_scope().block_on() {
+            log::error!("Shader doesn't fit the pipeline, using the built-in shader: {}", e);
+            return create(window, None);
+        }
+
+        pipeline
+    }
+
+    /// Draws one scene in its own render pass.
+    /// The depth buffer is cleared for every layer, so a layer is always drawn over the previous ones.
+    fn render_layer(
+        &self,
+        window: &mut Window,
+        layer: &mut SceneLayer,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+        depth_view: &wgpu::TextureView,
+        clear_color: Option<wgpu::Color>,
+    ) -> anyhow::Result<()> {
+        let camera = layer.camera;
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: match clear_color {
+                        Some(color) => wgpu::LoadOp::Clear(color),
+                        None => wgpu::LoadOp::Load,
+                    },
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        let world_raw_ptr = layer.world as *mut hecs::World;
+
+        unsafe {
+            render_pass.set_pipeline(self.pipelines.get(&Sprite::type_id()).unwrap());
+
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut Sprite, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+
+            // Bitmap text and nine slice sprites are drawn with the sprite pipeline
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut BitmapText, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut NineSlice, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+
+            render_pass
+                .set_pipeline(self.pipelines.get(&TranslucentSprite::type_id()).unwrap());
+
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut TranslucentSprite, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+
+            render_pass.set_pipeline(self.pipelines.get(&Text::type_id()).unwrap());
+
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut Text, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+
+            render_pass.set_pipeline(self.pipelines.get(&Rect::type_id()).unwrap());
+
+            for (i, (renderable, transform)) in
+                (&mut *world_raw_ptr).query_mut::<(&mut Rect, &mut Transform)>()
+            {
+                renderable.render(window, camera, transform, &mut render_pass)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Renderer for Renderer2D {
@@ -63,23 +247,6 @@ impl Renderer for Renderer2D {
         ));
 
         self.create_depth_buffer(window);
-
-        self.text_renderer = Some(glyphon::TextRenderer::new(
-            self.text_atlas.as_mut().unwrap(),
-            &window.device,
-            wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            Some(wgpu::DepthStencilState {
-                format: Self::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-        ));
     }
 
     fn resize_callback(&mut self, window: &Window) {
@@ -88,12 +255,55 @@ impl Renderer for Renderer2D {
         self.create_depth_buffer(window);
     }
 
+    /// Bitmap text and nine slice sprites are drawn with the sprite pipeline, set the shader for `Sprite`.
+    fn set_shader(&mut self, renderable: TypeId, shader: Asset<Shader>) -> anyhow::Result<()> {
+        let pipelines = [
+            Sprite::type_id(),
+            TranslucentSprite::type_id(),
+            Text::type_id(),
+            Rect::type_id(),
+        ];
+
+        if !pipelines.contains(&renderable) {
+            return Err(anyhow::anyhow!(
+                "Renderer2D has no pipeline of its own for this type, only Sprite, TranslucentSprite, Text and Rect do."
+            ));
+        }
+
+        self.shaders.insert(renderable, shader);
+        self.pipelines.remove(&renderable);
+
+        Ok(())
+    }
+
     fn render(
         &mut self,
         window: &mut Window,
-        camera: &Camera,
-        world: &mut hecs::World,
+        layers: &mut [SceneLayer],
+        overlay: Option<ScreenOverlay>,
     ) -> anyhow::Result<()> {
+        // Text is rasterized to textures before the main pass, which then draws them as quads
+        let text_atlas = self.text_atlas.as_mut().unwrap_or_else(|| {
+            log::error!("Text atlas not initialized!");
+            panic!("Text atlas not initialized!")
+        });
+
+        for layer in layers.iter_mut() {
+            for (_, (text, transform)) in layer.world.query_mut::<(&mut Text, &Transform)>() {
+                text.prepare(
+                    window,
+                    layer.camera,
+                    transform,
+                    &mut self.font_system,
+                    &mut self.swash_cache,
+                    &mut self.loaded_fonts,
+                    text_atlas,
+                )?;
+            }
+        }
+
+        self.create_pipelines(window)?;
+
         let output = window.surface.get_current_texture()?;
 
         let output_view = output
@@ -114,9 +324,25 @@ impl Renderer for Renderer2D {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
+
+        for (i, layer) in layers.iter_mut().enumerate() {
+            // Only the first layer clears the screen, the others are drawn over it
+            let clear_color = if i == 0 { Some(wgpu::Color::BLACK) } else { None };
+
+            self.render_layer(
+                window,
+                layer,
+                &mut encoder,
+                &output_view,
+                &depth_view,
+                clear_color,
+            )?;
+        }
+
+        if layers.is_empty() {
+            // Still clear the screen so the overlay isn't drawn over garbage
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Clear Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &output_view,
                     resolve_target: None,
@@ -125,86 +351,28 @@ impl Renderer for Renderer2D {
                         store: wgpu::StoreOp::Store,
                     },
                 })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
+                depth_stencil_attachment: None,
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
+        }
 
-            // TODO: Make a macro that does this for a bunch of types automatically
-            if !self.pipelines.contains_key(&Sprite::type_id()) {
-                // Generate pipeline
-                let pipeline = Sprite::create_pipeline(window)?;
-
-                self.pipelines
-                    .extend(std::iter::once((Sprite::type_id(), pipeline)));
-            }
-
-            if !self.pipelines.contains_key(&TranslucentSprite::type_id()) {
-                // Generate pipeline
-                let pipeline = TranslucentSprite::create_pipeline(window)?;
-
-                self.pipelines
-                    .extend(std::iter::once((TranslucentSprite::type_id(), pipeline)));
-            }
-
-            if !self.pipelines.contains_key(&Rect::type_id()) {
-                // Generate pipeline
-                let pipeline = Rect::create_pipeline(window)?;
-
-                self.pipelines
-                    .extend(std::iter::once((Rect::type_id(), pipeline)));
-            }
+        if let Some(overlay) = &overlay {
+            let overlay_renderer = self
+                .overlay
+                .get_or_insert_with(|| OverlayRenderer::new(window));
 
-            let world_raw_ptr = world as *mut hecs::World;
-            let self_raw_ptr = self as *mut Renderer2D;
-
-            unsafe {
-                render_pass.set_pipeline(self.pipelines.get(&Sprite::type_id()).unwrap());
-
-                for (i, (renderable, transform)) in
-                    (&mut *world_raw_ptr).query_mut::<(&mut Sprite, &mut Transform)>()
-                {
-                    renderable.render(window, camera, transform, &mut render_pass)?;
-                }
-
-                render_pass
-                    .set_pipeline(self.pipelines.get(&TranslucentSprite::type_id()).unwrap());
-
-                for (i, (renderable, transform)) in
-                    (&mut *world_raw_ptr).query_mut::<(&mut TranslucentSprite, &mut Transform)>()
-                {
-                    renderable.render(window, camera, transform, &mut render_pass)?;
-                }
-
-                for (i, (renderable, transform)) in
-                    (&mut *world_raw_ptr).query_mut::<(&mut Text, &mut Transform)>()
-                {
-                    let self_ref = &mut *self_raw_ptr;
-                    let text_atlas = self_ref.text_atlas.as_mut().unwrap();
-                    let text_renderer = self_ref.text_renderer.as_mut().unwrap();
-
-                    renderable.render(window, camera, transform, &mut self_ref.font_system, &mut self_ref.swash_cache, text_atlas, text_renderer, &mut render_pass)?;
-                }
-
-                render_pass.set_pipeline(self.pipelines.get(&Rect::type_id()).unwrap());
-                
-                for (i, (renderable, transform)) in
-                    (&mut *world_raw_ptr).query_mut::<(&mut Rect, &mut Transform)>()
-                {
-                    renderable.render(window, camera, transform, &mut render_pass)?;
-                }
-            }
+            overlay_renderer.render(window, overlay, &mut encoder, &output_view);
         }
+
         window.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
+        // Free glyphs that weren't used this frame
+        if let Some(text_atlas) = &mut self.text_atlas {
+            text_atlas.trim();
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,124 @@
+use crate::{renderer::ScreenOverlay, uniform::Uniform, window::Window};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct OverlayUniform {
+    color: [f32; 4],
+    rect: [f32; 4],
+}
+
+impl From<&ScreenOverlay> for OverlayUniform {
+    fn from(overlay: &ScreenOverlay) -> Self {
+        Self {
+            color: overlay.color,
+            rect: overlay.rect,
+        }
+    }
+}
+
+/// Draws a colored rectangle over everything, used for scene transitions.
+pub(crate) struct OverlayRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniform: Uniform<OverlayUniform>,
+}
+
+impl OverlayRenderer {
+    pub(crate) fn new(window: &Window) -> Self {
+        let shader = window
+            .device
+            .create_shader_module(wgpu::include_wgsl!("renderables/shaders/overlay.wgsl"));
+
+        let uniform_bind_group_layout = Uniform::<OverlayUniform>::get_bind_group_layout(
+            &window.device,
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+        );
+
+        let render_pipeline_layout =
+            window
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Overlay Render Pipeline Layout"),
+                    bind_group_layouts: &[&uniform_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let pipeline = window
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Overlay Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: window.config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // Drawn in its own pass after the scenes, so it doesn't need the depth buffer
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+            });
+
+        let uniform = Uniform::new(
+            &window.device,
+            &OverlayUniform {
+                color: [0.0; 4],
+                rect: [0.0; 4],
+            },
+            0,
+            wgpu::ShaderStages::VERTEX_FRAGMENT,
+        );
+
+        Self { pipeline, uniform }
+    }
+
+    pub(crate) fn render(
+        &self,
+        window: &Window,
+        overlay: &ScreenOverlay,
+        encoder: &mut wgpu::CommandEncoder,
+        output_view: &wgpu::TextureView,
+    ) {
+        self.uniform.update(&OverlayUniform::from(overlay), &window.queue);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.uniform.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+    }
+}
@@ -0,0 +1,362 @@
+use std::ops::Range;
+
+use ultraviolet::Mat4;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::Asset, camera::Camera, renderer_2d::bitmap_font::BitmapFont, transform::Transform,
+    uniform::Uniform, window::Window,
+};
+
+use super::{Renderable, Sprite, SpriteUniform, TextAlign, UvVertex, DEFAULT_ALPHA_CUTOFF};
+
+struct BitmapTextRenderInfo {
+    /// None when there are no visible glyphs
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    /// Index ranges to draw for each atlas page
+    page_ranges: Vec<(usize, Range<u32>)>,
+
+    /// Automatically synced during render
+    model_matrix_uniform: Uniform<Mat4>,
+    sprite_uniform: Uniform<SpriteUniform>,
+}
+
+/// Text drawn from a pre-baked `BitmapFont` with the sprite pipeline.
+/// Every glyph is a textured quad, so the text is depth tested and sorted exactly like sprites.
+pub struct BitmapText {
+    font: Asset<BitmapFont>,
+    text: String,
+    pixels_per_unit: f32,
+    align: TextAlign,
+    centered_origin: bool,
+    alpha_cutoff: f32,
+
+    render_info: Option<BitmapTextRenderInfo>,
+    _dirty_mesh: bool,
+    _dirty_sprite_uniform: bool,
+}
+
+impl<'a> BitmapText {
+    pub fn new<S>(font: Asset<BitmapFont>, text: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            font,
+            text: text.into(),
+            pixels_per_unit: 16.0,
+            align: TextAlign::Left,
+            centered_origin: true,
+            alpha_cutoff: DEFAULT_ALPHA_CUTOFF,
+
+            render_info: None,
+            _dirty_mesh: true,
+            _dirty_sprite_uniform: true,
+        }
+    }
+
+    pub fn set_text<S>(&mut self, text: S)
+    where
+        S: Into<String>,
+    {
+        self.text = text.into();
+        self._dirty_mesh = true;
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn set_font(&mut self, font: Asset<BitmapFont>) {
+        self.font = font;
+        self._dirty_mesh = true;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn font(&self) -> &Asset<BitmapFont> {
+        &self.font
+    }
+
+    /// Sets how many font pixels fit in one world unit.
+    /// For pixel perfect text, match the number of screen pixels per world unit.
+    pub fn set_pixels_per_unit(&mut self, pixels_per_unit: f32) {
+        self.pixels_per_unit = pixels_per_unit;
+        self._dirty_mesh = true;
+    }
+
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.pixels_per_unit
+    }
+
+    /// Justified text is laid out left aligned.
+    pub fn set_align(&mut self, align: TextAlign) {
+        self.align = align;
+        self._dirty_mesh = true;
+    }
+
+    pub fn align(&self) -> TextAlign {
+        self.align
+    }
+
+    /// If true, the transform's position is the center of the text block.
+    /// Otherwise it is the top left corner.
+    pub fn set_centered_origin(&mut self, centered_origin: bool) {
+        self.centered_origin = centered_origin;
+        self._dirty_mesh = true;
+    }
+
+    pub fn centered_origin(&self) -> bool {
+        self.centered_origin
+    }
+
+    /// Pixels with an alpha below the cutoff are not drawn and don't write depth.
+    /// Defaults to `DEFAULT_ALPHA_CUTOFF`.
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.alpha_cutoff = alpha_cutoff;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn alpha_cutoff(&self) -> f32 {
+        self.alpha_cutoff
+    }
+
+    fn sprite_uniform_data(&self) -> SpriteUniform {
+        // All pages of a font are imported the same way
+        let premultiplied = self
+            .font
+            .pages
+            .first()
+            .map(|page| page.settings().premultiply_alpha)
+            .unwrap_or(false);
+
+        SpriteUniform::new(self.alpha_cutoff, premultiplied)
+    }
+
+    /// Size of the text block in world units, before the transform's scale.
+    pub fn size(&self) -> (f32, f32) {
+        let (width, height) = self.font.measure(&self.text);
+
+        (width / self.pixels_per_unit, height / self.pixels_per_unit)
+    }
+
+    /// Lays out one quad per glyph, grouped by atlas page.
+    fn build_mesh(&self) -> (Vec<UvVertex>, Vec<u32>, Vec<(usize, Range<u32>)>) {
+        let font = &self.font;
+        let (atlas_width, atlas_height) = font.atlas_size();
+        let (block_width, block_height) = font.measure(&self.text);
+
+        // Origin offset in font pixels, y grows downwards until converted
+        let (origin_x, origin_y) = if self.centered_origin {
+            (block_width / 2.0, block_height / 2.0)
+        } else {
+            (0.0, 0.0)
+        };
+
+        let mut page_quads: Vec<Vec<[UvVertex; 4]>> = vec![Vec::new(); font.pages.len()];
+
+        for (line_index, line) in self.text.split('\n').enumerate() {
+            let line_width = font.line_width(line);
+
+            let mut pen_x = match self.align {
+                TextAlign::Left | TextAlign::Justified => 0.0,
+                TextAlign::Center => (block_width - line_width) / 2.0,
+                TextAlign::Right => block_width - line_width,
+            };
+            let pen_y = line_index as f32 * font.line_height();
+
+            let mut previous = None;
+
+            for c in line.chars() {
+                let glyph = if let Some(glyph) = font.glyph(c) {
+                    glyph
+                } else {
+                    previous = Some(c);
+                    continue;
+                };
+
+                if let Some(previous) = previous {
+                    pen_x += font.kerning(previous, c);
+                }
+                previous = Some(c);
+
+                if glyph.width > 0 && glyph.height > 0 {
+                    let left = (pen_x + glyph.x_offset - origin_x) / self.pixels_per_unit;
+                    let right = left + glyph.width as f32 / self.pixels_per_unit;
+                    let top = -(pen_y + glyph.y_offset - origin_y) / self.pixels_per_unit;
+                    let bottom = top - glyph.height as f32 / self.pixels_per_unit;
+
+                    let u0 = glyph.x as f32 / atlas_width;
+                    let v0 = glyph.y as f32 / atlas_height;
+                    let u1 = (glyph.x + glyph.width) as f32 / atlas_width;
+                    let v1 = (glyph.y + glyph.height) as f32 / atlas_height;
+
+                    // Same winding as the sprite quad
+                    page_quads[glyph.page].push([
+                        UvVertex {
+                            position: [left, top, 0.0],
+                            uv: [u0, v0],
+                        },
+                        UvVertex {
+                            position: [left, bottom, 0.0],
+                            uv: [u0, v1],
+                        },
+                        UvVertex {
+                            position: [right, bottom, 0.0],
+                            uv: [u1, v1],
+                        },
+                        UvVertex {
+                            position: [right, top, 0.0],
+                            uv: [u1, v0],
+                        },
+                    ]);
+                }
+
+                pen_x += glyph.x_advance;
+            }
+        }
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut page_ranges = Vec::new();
+
+        for (page, quads) in page_quads.into_iter().enumerate() {
+            if quads.is_empty() {
+                continue;
+            }
+
+            let start = indices.len() as u32;
+
+            for quad in quads {
+                let base = vertices.len() as u32;
+                vertices.extend_from_slice(&quad);
+                indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+            }
+
+            page_ranges.push((page, start..indices.len() as u32));
+        }
+
+        (vertices, indices, page_ranges)
+    }
+
+    fn update_mesh(&mut self, window: &Window) {
+        let (vertices, indices, page_ranges) = self.build_mesh();
+
+        let (vertex_buffer, index_buffer) = if indices.is_empty() {
+            (None, None)
+        } else {
+            let vertex_buffer = window
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Bitmap Text Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+
+            let index_buffer = window
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Bitmap Text Index Buffer"),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                });
+
+            (Some(vertex_buffer), Some(index_buffer))
+        };
+
+        if let Some(render_info) = &mut self.render_info {
+            render_info.vertex_buffer = vertex_buffer;
+            render_info.index_buffer = index_buffer;
+            render_info.page_ranges = page_ranges;
+        } else {
+            let model_matrix_uniform = Uniform::<Mat4>::new(&window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
+            let sprite_uniform = Uniform::<SpriteUniform>::new(
+                &window.device,
+                &self.sprite_uniform_data(),
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+            );
+
+            self.render_info = Some(BitmapTextRenderInfo {
+                vertex_buffer,
+                index_buffer,
+                page_ranges,
+                model_matrix_uniform,
+                sprite_uniform,
+            });
+        }
+    }
+}
+
+impl<'a> Renderable<'a> for BitmapText {
+    fn render(
+        &'a mut self,
+        window: &mut crate::window::Window,
+        camera: &'a Camera,
+        transform: &'a mut Transform,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) -> anyhow::Result<()> {
+        let first_render = self.render_info.is_none();
+
+        if self.font.refresh() {
+            self._dirty_mesh = true;
+            self._dirty_sprite_uniform = true;
+        }
+
+        if self._dirty_mesh || first_render {
+            self.update_mesh(window);
+            self._dirty_mesh = false;
+        }
+
+        if self._dirty_sprite_uniform {
+            self.render_info
+                .as_ref()
+                .unwrap()
+                .sprite_uniform
+                .update(&self.sprite_uniform_data(), &window.queue);
+            self._dirty_sprite_uniform = false;
+        }
+
+        let render_info = self.render_info.as_ref().unwrap();
+
+        // The uniform starts as identity, so it has to be synced on the first render
+        // even if something else already recalculated the matrix
+        if transform.recalculate_matrix() || first_render {
+            render_info
+                .model_matrix_uniform
+                .update(&transform.model_matrix(), &window.queue);
+        }
+
+        let (vertex_buffer, index_buffer) =
+            match (&render_info.vertex_buffer, &render_info.index_buffer) {
+                (Some(vertex_buffer), Some(index_buffer)) => (vertex_buffer, index_buffer),
+                _ => return Ok(()),
+            };
+
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
+        render_pass.set_bind_group(2, &render_info.model_matrix_uniform.bind_group, &[]);
+        render_pass.set_bind_group(3, &render_info.sprite_uniform.bind_group, &[]);
+
+        for (page, range) in &render_info.page_ranges {
+            render_pass.set_bind_group(0, &self.font.pages[*page].bind_group, &[]);
+            render_pass.draw_indexed(range.clone(), 0, 0..1);
+        }
+
+        Ok(())
+    }
+
+    fn type_id() -> std::any::TypeId {
+        std::any::TypeId::of::<Self>()
+    }
+
+    /// Bitmap text shares the sprite pipeline.
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        Sprite::create_pipeline(window, shader)
+    }
+}
@@ -3,9 +3,13 @@ pub use sprite::Sprite;
 pub mod translucent_sprite;
 pub use translucent_sprite::TranslucentSprite;
 pub mod text;
-pub use text::Text;
+pub use text::{Text, TextAlign, TextSpan, TextWrap, VerticalAlign};
 pub mod rect;
 pub use rect::Rect;
+pub mod bitmap_text;
+pub use bitmap_text::BitmapText;
+pub mod nine_slice;
+pub use nine_slice::{NineSlice, NineSliceInsets, NineSliceMode};
 
 
 use crate::{window::Window, camera::Camera, transform::Transform};
@@ -26,7 +30,36 @@ pub trait Renderable<'a> {
     // Generate the render pipeline at the start, store in a hashmap with the type_id
     // If the type_id doesn't exist in the hash, the renderer will call the get_pipeline function.
     // When rendering next, get the pipeline from the hashmap
-    fn create_pipeline(window: &mut Window) -> anyhow::Result<wgpu::RenderPipeline>;
+    // The shader replaces the built-in one if it was set with `Renderer::set_shader`
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline>;
+}
+
+/// Alpha below which sprite pixels are discarded when no other cutoff is set.
+pub const DEFAULT_ALPHA_CUTOFF: f32 = 0.01;
+
+/// Per draw settings for everything drawn with `sprite.wgsl`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct SpriteUniform {
+    /// Pixels with a lower alpha are discarded and don't write depth
+    pub alpha_cutoff: f32,
+    /// 1 if the texture colors are premultiplied by alpha
+    pub premultiplied: u32,
+    // Uniform buffers are laid out in blocks of 16 bytes
+    _padding: [u32; 2],
+}
+
+impl SpriteUniform {
+    pub(crate) fn new(alpha_cutoff: f32, premultiplied: bool) -> Self {
+        Self {
+            alpha_cutoff,
+            premultiplied: premultiplied as u32,
+            _padding: [0; 2],
+        }
+    }
 }
 
 #[repr(C)]
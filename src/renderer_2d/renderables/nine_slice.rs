@@ -0,0 +1,367 @@
+use ultraviolet::Mat4;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    assets::Asset, camera::Camera, texture::Texture, transform::Transform, uniform::Uniform,
+    window::Window,
+};
+
+use super::{Renderable, Sprite, SpriteUniform, UvVertex, DEFAULT_ALPHA_CUTOFF};
+
+/// Most tiles drawn along one axis, so tiny tiles on a huge panel can't explode the mesh.
+const MAX_TILES: usize = 256;
+
+/// Border sizes in texture pixels, measured from each edge of the texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NineSliceInsets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl NineSliceInsets {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// The same inset on all four sides.
+    pub fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+}
+
+/// How the edges and the centre fill the space between the corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NineSliceMode {
+    Stretch,
+    /// Repeats the slice at its original size, the last tile is cut off
+    Tile,
+}
+
+/// A piece of one axis of the mesh: world start and end, and the uv start and end.
+type Segment = (f32, f32, f32, f32);
+
+struct NineSliceRenderInfo {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+
+    /// Automatically synced during render
+    model_matrix_uniform: Uniform<Mat4>,
+    sprite_uniform: Uniform<SpriteUniform>,
+}
+
+/// A sprite split into nine parts by border insets.
+/// The transform's scale sets the size of the whole panel in world units, the corners
+/// keep their size while the edges and centre stretch or tile to fill the rest.
+/// It follows its parent like any other renderable, a scaled parent scales the corners too.
+pub struct NineSlice {
+    texture: Asset<Texture>,
+    insets: NineSliceInsets,
+    mode: NineSliceMode,
+    pixels_per_unit: f32,
+    alpha_cutoff: f32,
+
+    render_info: Option<NineSliceRenderInfo>,
+    /// Panel size the mesh was last built for
+    last_size: (f32, f32),
+    _dirty_mesh: bool,
+    _dirty_sprite_uniform: bool,
+}
+
+impl NineSlice {
+    pub fn new(texture: Asset<Texture>, insets: NineSliceInsets) -> Self {
+        Self {
+            texture,
+            insets,
+            mode: NineSliceMode::Stretch,
+            pixels_per_unit: 16.0,
+            alpha_cutoff: DEFAULT_ALPHA_CUTOFF,
+
+            render_info: None,
+            last_size: (0.0, 0.0),
+            _dirty_mesh: true,
+            _dirty_sprite_uniform: true,
+        }
+    }
+
+    pub fn set_texture(&mut self, texture: Asset<Texture>) {
+        self.texture = texture;
+        self._dirty_mesh = true;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn texture(&self) -> &Asset<Texture> {
+        &self.texture
+    }
+
+    pub fn set_insets(&mut self, insets: NineSliceInsets) {
+        self.insets = insets;
+        self._dirty_mesh = true;
+    }
+
+    pub fn insets(&self) -> NineSliceInsets {
+        self.insets
+    }
+
+    pub fn set_mode(&mut self, mode: NineSliceMode) {
+        self.mode = mode;
+        self._dirty_mesh = true;
+    }
+
+    pub fn mode(&self) -> NineSliceMode {
+        self.mode
+    }
+
+    /// Sets how many texture pixels fit in one world unit, this decides the size of the corners.
+    pub fn set_pixels_per_unit(&mut self, pixels_per_unit: f32) {
+        self.pixels_per_unit = pixels_per_unit;
+        self._dirty_mesh = true;
+    }
+
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.pixels_per_unit
+    }
+
+    /// Pixels with an alpha below the cutoff are not drawn and don't write depth.
+    /// Defaults to `DEFAULT_ALPHA_CUTOFF`.
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.alpha_cutoff = alpha_cutoff;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn alpha_cutoff(&self) -> f32 {
+        self.alpha_cutoff
+    }
+
+    /// Splits one axis into the start border, the tiled or stretched middle and the end border.
+    /// `start` and `end` are the insets and `texture_size` the texture size on this axis, in pixels.
+    fn axis_segments(&self, size: f32, start: f32, end: f32, texture_size: f32) -> Vec<Segment> {
+        let mut start_world = start / self.pixels_per_unit;
+        let mut end_world = end / self.pixels_per_unit;
+
+        // Shrink the borders if the panel is smaller than both of them together
+        if start_world + end_world > size {
+            let factor = size / (start_world + end_world);
+            start_world *= factor;
+            end_world *= factor;
+        }
+
+        let uv_start = start / texture_size;
+        let uv_end = (texture_size - end) / texture_size;
+
+        let min = -size / 2.0;
+        let max = size / 2.0;
+        let middle_start = min + start_world;
+        let middle_end = max - end_world;
+
+        let mut segments = vec![(min, middle_start, 0.0, uv_start)];
+
+        if middle_end > middle_start {
+            match self.mode {
+                NineSliceMode::Stretch => {
+                    segments.push((middle_start, middle_end, uv_start, uv_end));
+                }
+                NineSliceMode::Tile => {
+                    let tile = (texture_size - start - end) / self.pixels_per_unit;
+
+                    if tile <= 0.0 {
+                        segments.push((middle_start, middle_end, uv_start, uv_end));
+                    } else {
+                        let mut position = middle_start;
+
+                        for _ in 0..MAX_TILES {
+                            if position >= middle_end {
+                                break;
+                            }
+
+                            let next = (position + tile).min(middle_end);
+                            let fraction = (next - position) / tile;
+
+                            segments.push((
+                                position,
+                                next,
+                                uv_start,
+                                uv_start + (uv_end - uv_start) * fraction,
+                            ));
+
+                            position = next;
+                        }
+                    }
+                }
+            }
+        }
+
+        segments.push((middle_end, max, uv_end, 1.0));
+
+        segments
+    }
+
+    fn update_mesh(&mut self, window: &Window, size: (f32, f32)) {
+        let (texture_width, texture_height) = self.texture.size();
+        let (texture_width, texture_height) = (texture_width as f32, texture_height as f32);
+
+        let columns = self.axis_segments(size.0, self.insets.left, self.insets.right, texture_width);
+        // Rows are built bottom to top, the top inset is at the end of the axis
+        let rows = self.axis_segments(size.1, self.insets.bottom, self.insets.top, texture_height);
+
+        let mut vertices = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+
+        for (bottom, top, v_bottom, v_top) in &rows {
+            // Texture v grows downwards while world y grows upwards
+            let (v_bottom, v_top) = (1.0 - v_bottom, 1.0 - v_top);
+
+            for (left, right, u_left, u_right) in &columns {
+                if right <= left || top <= bottom {
+                    continue;
+                }
+
+                let base = vertices.len() as u32;
+
+                // Same winding as the sprite quad
+                vertices.extend_from_slice(&[
+                    UvVertex {
+                        position: [*left, *top, 0.0],
+                        uv: [*u_left, v_top],
+                    },
+                    UvVertex {
+                        position: [*left, *bottom, 0.0],
+                        uv: [*u_left, v_bottom],
+                    },
+                    UvVertex {
+                        position: [*right, *bottom, 0.0],
+                        uv: [*u_right, v_bottom],
+                    },
+                    UvVertex {
+                        position: [*right, *top, 0.0],
+                        uv: [*u_right, v_top],
+                    },
+                ]);
+
+                indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+            }
+        }
+
+        let vertex_buffer = window
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Nine Slice Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let index_buffer = window
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Nine Slice Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        if let Some(render_info) = &mut self.render_info {
+            render_info.vertex_buffer = vertex_buffer;
+            render_info.index_buffer = index_buffer;
+            render_info.index_count = indices.len() as u32;
+        } else {
+            let model_matrix_uniform = Uniform::<Mat4>::new(&window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
+            let sprite_uniform = Uniform::<SpriteUniform>::new(
+                &window.device,
+                &SpriteUniform::new(self.alpha_cutoff, self.texture.settings().premultiply_alpha),
+                0,
+                wgpu::ShaderStages::FRAGMENT,
+            );
+
+            self.render_info = Some(NineSliceRenderInfo {
+                vertex_buffer,
+                index_buffer,
+                index_count: indices.len() as u32,
+                model_matrix_uniform,
+                sprite_uniform,
+            });
+        }
+
+        self.last_size = size;
+    }
+}
+
+impl<'a> Renderable<'a> for NineSlice {
+    fn render(
+        &'a mut self,
+        window: &mut crate::window::Window,
+        camera: &'a Camera,
+        transform: &'a mut Transform,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) -> anyhow::Result<()> {
+        // The scale is baked into the mesh so the corners don't get scaled
+        let size = (transform.scale().x.abs(), transform.scale().y.abs());
+
+        let first_render = self.render_info.is_none();
+
+        // A hot reloaded texture may have a different size or import settings
+        if self.texture.refresh() {
+            self._dirty_mesh = true;
+            self._dirty_sprite_uniform = true;
+        }
+
+        if self._dirty_mesh || first_render || size != self.last_size {
+            self.update_mesh(window, size);
+            self._dirty_mesh = false;
+        }
+
+        if self._dirty_sprite_uniform {
+            self.render_info.as_ref().unwrap().sprite_uniform.update(
+                &SpriteUniform::new(self.alpha_cutoff, self.texture.settings().premultiply_alpha),
+                &window.queue,
+            );
+            self._dirty_sprite_uniform = false;
+        }
+
+        let render_info = self.render_info.as_ref().unwrap();
+
+        if transform.recalculate_matrix() || first_render {
+            // The own scale is already in the mesh, only the parent's is applied here
+            let model_matrix = *transform.parent_matrix()
+                * Mat4::from_translation(*transform.position())
+                * transform.rotation().into_matrix().into_homogeneous();
+
+            render_info
+                .model_matrix_uniform
+                .update(&model_matrix, &window.queue);
+        }
+
+        if render_info.index_count == 0 {
+            return Ok(());
+        }
+
+        render_pass.set_vertex_buffer(0, render_info.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(render_info.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        render_pass.set_bind_group(0, &self.texture.bind_group, &[]);
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
+        render_pass.set_bind_group(2, &render_info.model_matrix_uniform.bind_group, &[]);
+        render_pass.set_bind_group(3, &render_info.sprite_uniform.bind_group, &[]);
+
+        render_pass.draw_indexed(0..render_info.index_count, 0, 0..1);
+
+        Ok(())
+    }
+
+    fn type_id() -> std::any::TypeId {
+        std::any::TypeId::of::<Self>()
+    }
+
+    /// Nine slice sprites share the sprite pipeline.
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        Sprite::create_pipeline(window, shader)
+    }
+}
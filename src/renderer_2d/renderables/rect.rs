@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use ultraviolet::Mat4;
 use wgpu::util::DeviceExt;
 
@@ -37,9 +38,12 @@ struct RectRenderInfo {
     color_uniform: Uniform<[f32; 4]>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Rect {
     color: [f32; 4],
+    #[serde(skip)]
     _dirty_color: bool,
+    #[serde(skip)]
     render_info: Option<RectRenderInfo>,
 }
 
@@ -52,12 +56,13 @@ impl<'a> Rect {
         }
     }
 
-    fn set_color(&mut self, color: (f32, f32, f32, f32)) {
+    /// Each channel is in the range of 0.0 to 1.0.
+    pub fn set_color(&mut self, color: (f32, f32, f32, f32)) {
         self.color = [color.0, color.1, color.2, color.3];
         self._dirty_color = true;
     }
 
-    fn color(&self) -> (f32, f32, f32, f32) {
+    pub fn color(&self) -> (f32, f32, f32, f32) {
         (self.color[0], self.color[1], self.color[2], self.color[3])
     }
 
@@ -136,10 +141,20 @@ impl<'a> Renderable<'a> for Rect {
         std::any::TypeId::of::<Self>()
     }
 
-    fn create_pipeline(window: &mut Window) -> anyhow::Result<wgpu::RenderPipeline> {
-        let shader = window
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shaders/rect.wgsl"));
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let built_in;
+        let shader = match shader {
+            Some(shader) => shader,
+            None => {
+                built_in = window
+                    .device
+                    .create_shader_module(wgpu::include_wgsl!("shaders/rect.wgsl"));
+                &built_in
+            }
+        };
 
         let camera_bind_group_layout = Camera::get_bind_group_layout(&window.device);
         let model_matrix_bind_group_layout = Uniform::<Mat4>::get_bind_group_layout(&window.device, wgpu::ShaderStages::VERTEX);
@@ -165,12 +180,12 @@ impl<'a> Renderable<'a> for Rect {
                     label: Some("Render Pipeline"),
                     layout: Some(&render_pipeline_layout),
                     vertex: wgpu::VertexState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "vs_main",
                         buffers: &[UvVertex::desc()],
                     },
                     fragment: Some(wgpu::FragmentState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "fs_main",
                         targets: &[Some(wgpu::ColorTargetState {
                             format: window.config.format,
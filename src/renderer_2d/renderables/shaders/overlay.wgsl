@@ -0,0 +1,34 @@
+struct OverlayUniform {
+    color: vec4<f32>,
+    // Left, top, right, bottom in screen space, from 0 to 1 with the origin at the top left
+    rect: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> overlay: OverlayUniform;
+
+// Vertex shader
+// The quad is generated from the vertex index, so no vertex buffer is bound
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> @builtin(position) vec4<f32> {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+
+    let corner = corners[in_vertex_index];
+    let x = mix(overlay.rect.x, overlay.rect.z, corner.x);
+    let y = mix(overlay.rect.y, overlay.rect.w, corner.y);
+
+    return vec4<f32>(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0, 1.0);
+}
+
+// Fragment shader
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return overlay.color;
+}
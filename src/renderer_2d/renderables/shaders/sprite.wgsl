@@ -15,6 +15,14 @@ var<uniform> camera: CameraUniform;
 @group(2) @binding(0)
 var<uniform> model: mat4x4<f32>;
 
+struct SpriteUniform {
+    alpha_cutoff: f32,
+    premultiplied: u32,
+}
+
+@group(3) @binding(0)
+var<uniform> sprite: SpriteUniform;
+
 // Vertex shader
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -42,7 +50,7 @@ fn vs_main(
 // Fragment shader
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let color = textureSample(texture, tex_sampler, in.uv);
+    var color = textureSample(texture, tex_sampler, in.uv);
 
     // Either sprites need to be sorted, or alpha testing needs to be implemented
     // Just depth buffer doesn't work because on sprites with transparent pixels,
@@ -50,15 +58,17 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // So when another sprite is rendered behind these transparent pixels,
     // depth testing fails because the transparent pixels are closer to the camera.
 
-    // Is 0.01 a good value?
-    // Maybe it should be a parameter of the shader?
-    // TODO: Add alpha testing uniform
-    if (color.a < 0.01) {
+    if (color.a < sprite.alpha_cutoff) {
         discard;
         // Discarding this fragment means the depth value for this pixel will
         // not be updated, so the transparent pixels will not block other sprites
     }
 
+    // The pipelines use straight alpha blending, so undo the premultiplication.
+    // Filtering premultiplied colors first is what avoids the dark fringes.
+    if (sprite.premultiplied != 0u && color.a > 0.0) {
+        color = vec4<f32>(color.rgb / color.a, color.a);
+    }
 
     return color;
 }
\ No newline at end of file
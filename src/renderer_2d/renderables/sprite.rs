@@ -1,11 +1,14 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
 use ultraviolet::Mat4;
 use wgpu::util::DeviceExt;
 
 use crate::{
-    assets::Asset, camera::Camera, texture::Texture, window::Window, App, transform::Transform, uniform::Uniform, Renderer2D
+    assets::Asset, camera::Camera, scene::{SceneComponent, SceneLoadContext, SceneSaveContext}, texture::Texture, window::Window, App, transform::Transform, uniform::Uniform, Renderer2D
 };
 
-use super::{UvVertex, Renderable};
+use super::{Renderable, SpriteUniform, UvVertex, DEFAULT_ALPHA_CUTOFF};
 
 const RECT_VERTICES: &[UvVertex] = &[
     UvVertex {
@@ -35,12 +38,20 @@ pub struct Sprite {
 
     /// Automatically synced during render
     model_matrix_uniform: Uniform<Mat4>,
+
+    alpha_cutoff: f32,
+    sprite_uniform: Uniform<SpriteUniform>,
+    _dirty_sprite_uniform: bool,
 }
 
 impl Sprite {
     pub fn new(app: &App, texture: Asset<Texture>) -> Self {
+        Self::with_window(&app.window, texture)
+    }
+
+    fn with_window(window: &Window, texture: Asset<Texture>) -> Self {
         let vertex_buffer =
-            app.window
+            window
                 .device
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("Sprite Vertex Buffer"),
@@ -49,7 +60,7 @@ impl Sprite {
                 });
 
         let index_buffer =
-            app.window
+            window
                 .device
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: Some("Sprite Index Buffer"),
@@ -57,15 +68,73 @@ impl Sprite {
                     usage: wgpu::BufferUsages::INDEX,
                 });
 
-        let transform_uniform = Uniform::<Mat4>::new(&app.window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
+        let transform_uniform = Uniform::<Mat4>::new(&window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
+
+        let sprite_uniform = Uniform::<SpriteUniform>::new(
+            &window.device,
+            &SpriteUniform::new(DEFAULT_ALPHA_CUTOFF, texture.settings().premultiply_alpha),
+            0,
+            wgpu::ShaderStages::FRAGMENT,
+        );
 
         Self {
             texture: Some(texture),
             vertex_buffer,
             index_buffer,
             model_matrix_uniform: transform_uniform,
+
+            alpha_cutoff: DEFAULT_ALPHA_CUTOFF,
+            sprite_uniform,
+            _dirty_sprite_uniform: false,
         }
     }
+
+    /// Pixels with an alpha below the cutoff are not drawn and don't write depth.
+    /// Defaults to `DEFAULT_ALPHA_CUTOFF`.
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.alpha_cutoff = alpha_cutoff;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn alpha_cutoff(&self) -> f32 {
+        self.alpha_cutoff
+    }
+
+    pub fn texture(&self) -> Option<&Asset<Texture>> {
+        self.texture.as_ref()
+    }
+}
+
+/// Saved form of a `Sprite`, the texture is stored as the path it was loaded from.
+#[derive(Serialize, Deserialize)]
+pub struct SpriteData {
+    texture: PathBuf,
+    alpha_cutoff: f32,
+}
+
+impl SceneComponent for Sprite {
+    type Data = SpriteData;
+
+    fn save(&self, context: &SceneSaveContext) -> anyhow::Result<SpriteData> {
+        let texture = self
+            .texture
+            .as_ref()
+            .ok_or(anyhow::anyhow!("Sprite has no texture."))?;
+
+        Ok(SpriteData {
+            texture: context.asset_path(texture)?,
+            alpha_cutoff: self.alpha_cutoff,
+        })
+    }
+
+    fn restore(data: SpriteData, context: &mut SceneLoadContext) -> anyhow::Result<Self> {
+        let texture = context.load_asset::<Texture>(&data.texture)?;
+
+        let mut sprite = Self::with_window(context.window(), texture);
+        sprite.set_alpha_cutoff(data.alpha_cutoff);
+
+        Ok(sprite)
+    }
 }
 
 impl<'a> Renderable<'a> for Sprite {
@@ -76,6 +145,13 @@ impl<'a> Renderable<'a> for Sprite {
         transform: &'a mut Transform,
         render_pass: &mut wgpu::RenderPass<'a>,
     ) -> anyhow::Result<()> {
+        // Pick up hot reloaded textures
+        if let Some(texture) = &mut self.texture {
+            if texture.refresh() {
+                self._dirty_sprite_uniform = true;
+            }
+        }
+
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
@@ -91,6 +167,22 @@ impl<'a> Renderable<'a> for Sprite {
 
         render_pass.set_bind_group(2, &self.model_matrix_uniform.bind_group, &[]);
 
+        if self._dirty_sprite_uniform {
+            let premultiplied = self
+                .texture
+                .as_ref()
+                .map(|texture| texture.settings().premultiply_alpha)
+                .unwrap_or(false);
+
+            self.sprite_uniform.update(
+                &SpriteUniform::new(self.alpha_cutoff, premultiplied),
+                &window.queue,
+            );
+            self._dirty_sprite_uniform = false;
+        }
+
+        render_pass.set_bind_group(3, &self.sprite_uniform.bind_group, &[]);
+
         render_pass.draw_indexed(0..RECT_INDICES.len() as u32, 0, 0..1);
 
         Ok(())
@@ -100,21 +192,32 @@ impl<'a> Renderable<'a> for Sprite {
         std::any::TypeId::of::<Self>()
     }
 
-    fn create_pipeline(window: &mut Window) -> anyhow::Result<wgpu::RenderPipeline> {
-        let shader = window
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shaders/sprite.wgsl"));
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let built_in;
+        let shader = match shader {
+            Some(shader) => shader,
+            None => {
+                built_in = window
+                    .device
+                    .create_shader_module(wgpu::include_wgsl!("shaders/sprite.wgsl"));
+                &built_in
+            }
+        };
 
         let texture_bind_group_layout = Texture::get_bind_group_layout(&window.device);
         let camera_bind_group_layout = Camera::get_bind_group_layout(&window.device);
         let model_matrix_bind_group_layout = Uniform::<Mat4>::get_bind_group_layout(&window.device, wgpu::ShaderStages::VERTEX);
+        let sprite_bind_group_layout = Uniform::<SpriteUniform>::get_bind_group_layout(&window.device, wgpu::ShaderStages::FRAGMENT);
 
         let render_pipeline_layout =
             window
                 .device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Quad Render Pipeline Layout"),
-                    bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &model_matrix_bind_group_layout],
+                    bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &model_matrix_bind_group_layout, &sprite_bind_group_layout],
                     push_constant_ranges: &[],
                 });
 
@@ -125,12 +228,12 @@ impl<'a> Renderable<'a> for Sprite {
                     label: Some("Render Pipeline"),
                     layout: Some(&render_pipeline_layout),
                     vertex: wgpu::VertexState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "vs_main",
                         buffers: &[UvVertex::desc()],
                     },
                     fragment: Some(wgpu::FragmentState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "fs_main",
                         targets: &[Some(wgpu::ColorTargetState {
                             format: window.config.format,
@@ -1,24 +1,156 @@
-use ultraviolet::Mat4;
+use std::path::PathBuf;
+
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use ultraviolet::{Mat4, Vec3};
 use wgpu::util::DeviceExt;
 
 use crate::{
-    assets::Asset, camera::Camera, texture::Texture, transform::Transform, uniform::Uniform,
-    window::Window, App, Renderer2D,
+    assets::Asset, camera::Camera, renderer_2d::font::Font,
+    scene::{SceneComponent, SceneLoadContext, SceneSaveContext}, texture::Texture,
+    transform::Transform, uniform::Uniform, window::Window, App, Renderer2D,
 };
 
+use super::{Renderable, SpriteUniform, UvVertex, DEFAULT_ALPHA_CUTOFF};
+
+const RECT_VERTICES: &[UvVertex] = &[
+    UvVertex {
+        position: [-0.5, 0.5, 0.0],
+        uv: [0.0, 0.0],
+    }, // A
+    UvVertex {
+        position: [-0.5, -0.5, 0.0],
+        uv: [0.0, 1.0],
+    }, // B
+    UvVertex {
+        position: [0.5, -0.5, 0.0],
+        uv: [1.0, 1.0],
+    }, // C
+    UvVertex {
+        position: [0.5, 0.5, 0.0],
+        uv: [1.0, 0.0],
+    }, // D
+];
+
+const RECT_INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+/// Largest texture side the text is rasterized to, this is the wgpu default limit.
+const MAX_RASTER_SIZE: f32 = 8192.0;
+
+/// Horizontal alignment of each line inside the text bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    Justified,
+}
+
+/// Vertical alignment of the whole text block inside the text bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VerticalAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// How lines longer than the bounds width are broken up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextWrap {
+    /// Lines are never wrapped
+    None,
+    /// Lines are wrapped at any glyph
+    Glyph,
+    /// Lines are wrapped between words
+    Word,
+}
+
+/// A piece of rich text.
+/// Any style that is not set falls back to the style of the `Text` it is part of.
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<(f32, f32, f32, f32)>,
+    pub font: Option<Asset<Font>>,
+    pub weight: Option<u16>,
+    pub italic: Option<bool>,
+}
+
+impl TextSpan {
+    pub fn new<S>(text: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            text: text.into(),
+            color: None,
+            font: None,
+            weight: None,
+            italic: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: (f32, f32, f32, f32)) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_font(mut self, font: Asset<Font>) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    /// The weight uses the CSS scale, 400 is normal and 700 is bold.
+    pub fn with_weight(mut self, weight: u16) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    pub fn with_italic(mut self, italic: bool) -> Self {
+        self.italic = Some(italic);
+        self
+    }
+}
+
 pub struct Text {
     text_buffer: Option<glyphon::Buffer>,
     text: String,
+    spans: Option<Vec<TextSpan>>,
     bounds: (f32, f32),
     font_size: f32,
     line_height: f32,
     advanced_text: bool,
-    last_z: f32,
     centered_origin: bool,
+    pixels_per_unit: f32,
+
+    color: (f32, f32, f32, f32),
+    font: Option<Asset<Font>>,
+    weight: u16,
+    italic: bool,
+    align: TextAlign,
+    vertical_align: VerticalAlign,
+    wrap: TextWrap,
+
+    render_info: Option<TextRenderInfo>,
 
     _dirty_metrics: bool,
     _dirty_text: bool,
     _dirty_bounds: bool,
+    /// Set when the shaped text changed and the texture has to be redrawn
+    _dirty_raster: bool,
+}
+
+/// The text is rasterized into its own texture which is then drawn as a quad in the world,
+/// so it follows the transform's rotation and scale and is depth tested like a sprite.
+struct TextRenderInfo {
+    text_renderer: glyphon::TextRenderer,
+    target: Texture,
+
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+
+    /// Automatically synced during render
+    model_matrix_uniform: Uniform<Mat4>,
+    sprite_uniform: Uniform<SpriteUniform>,
 }
 
 impl<'a> Text {
@@ -29,16 +161,28 @@ impl<'a> Text {
         Self {
             text_buffer: None,
             text: text.into(),
+            spans: None,
             bounds,
             font_size,
             line_height,
             advanced_text: false,
-            last_z: 0.0,
             centered_origin: true,
+            pixels_per_unit: 100.0,
+
+            color: (1.0, 1.0, 1.0, 1.0),
+            font: None,
+            weight: 400,
+            italic: false,
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            wrap: TextWrap::Word,
+
+            render_info: None,
 
             _dirty_metrics: true,
             _dirty_text: true,
             _dirty_bounds: true,
+            _dirty_raster: true,
         }
     }
 
@@ -69,22 +213,54 @@ impl<'a> Text {
         self.advanced_text
     }
 
+    /// If true, the transform's position is the center of the bounds.
+    /// Otherwise it is the top left corner.
     pub fn set_centered_origin(&mut self, centered_origin: bool) {
         self.centered_origin = centered_origin;
     }
 
+    pub fn centered_origin(&self) -> bool {
+        self.centered_origin
+    }
+
+    /// Sets how many text pixels fit in one world unit.
+    /// Font size, line height and bounds are in text pixels, so a `Text` with bounds of
+    /// (200, 100) and 100 pixels per unit is 2 by 1 world units before the transform's scale.
+    pub fn set_pixels_per_unit(&mut self, pixels_per_unit: f32) {
+        self.pixels_per_unit = pixels_per_unit;
+    }
+
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.pixels_per_unit
+    }
+
+    /// Sets plain text, replacing any rich text spans.
     pub fn set_text<S>(&mut self, text: S)
     where
         S: Into<String>,
     {
         self.text = text.into();
+        self.spans = None;
         self._dirty_text = true;
     }
 
+    /// Returns the text without any styling.
     pub fn text(&self) -> &str {
         self.text.as_str()
     }
 
+    /// Sets rich text made of multiple styled spans.
+    /// Spans can contain line breaks.
+    pub fn set_rich_text(&mut self, spans: Vec<TextSpan>) {
+        self.text = spans.iter().map(|s| s.text.as_str()).collect();
+        self.spans = Some(spans);
+        self._dirty_text = true;
+    }
+
+    pub fn spans(&self) -> Option<&[TextSpan]> {
+        self.spans.as_deref()
+    }
+
     pub fn set_bounds(&mut self, bounds: (f32, f32)) {
         self.bounds = bounds;
         self._dirty_bounds = true;
@@ -94,49 +270,238 @@ impl<'a> Text {
         self.bounds
     }
 
+    /// Sets the default color of the text, each channel is in the range of 0.0 to 1.0.
+    pub fn set_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.color = color;
+        self._dirty_text = true;
+    }
+
+    pub fn color(&self) -> (f32, f32, f32, f32) {
+        self.color
+    }
+
+    /// Sets the default font. If None, the system sans-serif font is used.
+    pub fn set_font(&mut self, font: Option<Asset<Font>>) {
+        self.font = font;
+        self._dirty_text = true;
+    }
+
+    pub fn font(&self) -> Option<&Asset<Font>> {
+        self.font.as_ref()
+    }
+
+    /// Sets the default weight using the CSS scale, 400 is normal and 700 is bold.
+    pub fn set_weight(&mut self, weight: u16) {
+        self.weight = weight;
+        self._dirty_text = true;
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn set_italic(&mut self, italic: bool) {
+        self.italic = italic;
+        self._dirty_text = true;
+    }
+
+    pub fn italic(&self) -> bool {
+        self.italic
+    }
+
+    pub fn set_align(&mut self, align: TextAlign) {
+        self.align = align;
+        self._dirty_text = true;
+    }
+
+    pub fn align(&self) -> TextAlign {
+        self.align
+    }
+
+    pub fn set_vertical_align(&mut self, vertical_align: VerticalAlign) {
+        self.vertical_align = vertical_align;
+        self._dirty_raster = true;
+    }
+
+    pub fn vertical_align(&self) -> VerticalAlign {
+        self.vertical_align
+    }
+
+    pub fn set_wrap(&mut self, wrap: TextWrap) {
+        self.wrap = wrap;
+        self._dirty_text = true;
+    }
+
+    pub fn wrap(&self) -> TextWrap {
+        self.wrap
+    }
+
     pub fn initialize(&mut self, font_system: &'a mut glyphon::FontSystem) {
         self.text_buffer = Some(glyphon::Buffer::new(
             font_system,
-            glyphon::Metrics { 
+            glyphon::Metrics {
                 font_size: 32.0,
                 line_height: 40.0,
             }
         ));
     }
-    
-    pub(crate) fn render(
-        &'a mut self,
-        window: &mut crate::window::Window,
-        camera: &'a Camera,
-        transform: &'a mut Transform,
-        font_system: &'a mut glyphon::FontSystem,
-        swash_cache: &'a mut glyphon::SwashCache,
-        text_atlas: &'a mut glyphon::TextAtlas,
-        text_renderer: &'a mut glyphon::TextRenderer,
-        render_pass: &mut wgpu::RenderPass<'a>,
+
+    fn initialize_render_info(&mut self, window: &Window, text_atlas: &mut glyphon::TextAtlas) {
+        let vertex_buffer = window
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Text Vertex Buffer"),
+                contents: bytemuck::cast_slice(&RECT_VERTICES),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+
+        let index_buffer = window
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Text Index Buffer"),
+                contents: bytemuck::cast_slice(&RECT_INDICES),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+
+        let model_matrix_uniform = Uniform::<Mat4>::new(&window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
+
+        let target = Texture::create_render_target(window, 1, 1);
+
+        // The target is premultiplied, the shader converts it back for the sprite blending
+        let sprite_uniform = Uniform::<SpriteUniform>::new(
+            &window.device,
+            &SpriteUniform::new(DEFAULT_ALPHA_CUTOFF, target.settings().premultiply_alpha),
+            0,
+            wgpu::ShaderStages::FRAGMENT,
+        );
+
+        // The text is drawn to a texture with no depth attachment, depth is handled by the quad
+        let text_renderer = glyphon::TextRenderer::new(
+            text_atlas,
+            &window.device,
+            wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            None,
+        );
+
+        self.render_info = Some(TextRenderInfo {
+            text_renderer,
+            target,
+            vertex_buffer,
+            index_buffer,
+            model_matrix_uniform,
+            sprite_uniform,
+        });
+    }
+
+    /// Shapes the text and redraws its texture if the text or its size on screen changed.
+    /// Must be called before the main render pass, as the texture is drawn in its own submission.
+    pub(crate) fn prepare(
+        &mut self,
+        window: &Window,
+        camera: &Camera,
+        transform: &Transform,
+        font_system: &mut glyphon::FontSystem,
+        swash_cache: &mut glyphon::SwashCache,
+        loaded_fonts: &mut AHashSet<uuid::Uuid>,
+        text_atlas: &mut glyphon::TextAtlas,
     ) -> anyhow::Result<()> {
-        let text_buffer = if let Some(text_buffer) = &mut self.text_buffer {
-            text_buffer
-        } else {
+        if self.text_buffer.is_none() {
             self.initialize(font_system);
-            self.text_buffer.as_mut().unwrap()
-        };
+        }
+
+        if self.render_info.is_none() {
+            self.initialize_render_info(window, text_atlas);
+        }
 
-        if self.last_z != transform.position().z {
+        // Hot reloaded fonts have a new id, so they are registered again when shaping
+        let mut fonts_changed = self.font.as_mut().map(|font| font.refresh()).unwrap_or(false);
+        for span in self.spans.iter_mut().flatten() {
+            if let Some(font) = &mut span.font {
+                fonts_changed |= font.refresh();
+            }
+        }
+        if fonts_changed {
             self._dirty_text = true;
         }
 
+        let text_buffer = self.text_buffer.as_mut().unwrap();
+
         let need_to_shape = self._dirty_text || self._dirty_bounds || self._dirty_metrics;
 
         if self._dirty_text {
-            text_buffer.set_text(font_system, self.text.as_str(), glyphon::Attrs::new().metadata(transform.position().z.to_bits() as usize), glyphon::Shaping::Basic);
-            
+            // Fonts have to be in the font system before shaping, or the fallback font is used
+            if let Some(font) = &self.font {
+                font.ensure_loaded(font_system, loaded_fonts);
+            }
+
+            let shaping = if self.advanced_text {
+                glyphon::Shaping::Advanced
+            } else {
+                glyphon::Shaping::Basic
+            };
+
+            if let Some(spans) = &self.spans {
+                for span in spans {
+                    if let Some(font) = &span.font {
+                        font.ensure_loaded(font_system, loaded_fonts);
+                    }
+                }
+
+                text_buffer.set_rich_text(
+                    font_system,
+                    spans.iter().map(|span| {
+                        (
+                            span.text.as_str(),
+                            text_attrs(
+                                span.color.unwrap_or(self.color),
+                                span.font.as_ref().or(self.font.as_ref()),
+                                span.weight.unwrap_or(self.weight),
+                                span.italic.unwrap_or(self.italic),
+                            ),
+                        )
+                    }),
+                    shaping,
+                );
+            } else {
+                text_buffer.set_text(
+                    font_system,
+                    self.text.as_str(),
+                    text_attrs(self.color, self.font.as_ref(), self.weight, self.italic),
+                    shaping,
+                );
+            }
+
+            // Setting the text recreates the lines, so the alignment has to be reapplied
+            let align = match self.align {
+                TextAlign::Left => glyphon::cosmic_text::Align::Left,
+                TextAlign::Center => glyphon::cosmic_text::Align::Center,
+                TextAlign::Right => glyphon::cosmic_text::Align::Right,
+                TextAlign::Justified => glyphon::cosmic_text::Align::Justified,
+            };
+
+            for line in text_buffer.lines.iter_mut() {
+                line.set_align(Some(align));
+            }
+
+            text_buffer.set_wrap(
+                font_system,
+                match self.wrap {
+                    TextWrap::None => glyphon::Wrap::None,
+                    TextWrap::Glyph => glyphon::Wrap::Glyph,
+                    TextWrap::Word => glyphon::Wrap::Word,
+                },
+            );
+
             self._dirty_text = false;
         }
 
         if self._dirty_bounds {
             text_buffer.set_size(font_system, self.bounds.0, self.bounds.1);
-            
+
             self._dirty_bounds = false;
         }
 
@@ -145,58 +510,397 @@ impl<'a> Text {
                 font_size: self.font_size,
                 line_height: self.line_height,
             });
-            
+
             self._dirty_metrics = false;
         }
 
         if need_to_shape {
             text_buffer.shape_until_scroll(font_system);
+            self._dirty_raster = true;
         }
-        
-        let screen_size = window.winit_win.inner_size();
 
-        let mut screen_space_pos = camera.world_to_screen(window, transform.position());
+        // Rasterize at the resolution the text currently covers on screen,
+        // so it stays sharp when the camera zooms in or the transform is scaled up.
+        let screen_pixels_per_unit = window.config.height as f32 / camera.viewport_size().1;
+
+        let raster_scale = (screen_pixels_per_unit / self.pixels_per_unit
+            * transform.scale().y.abs())
+        .min(MAX_RASTER_SIZE / self.bounds.0.max(self.bounds.1).max(1.0));
+
+        let target_width = ((self.bounds.0 * raster_scale).ceil() as u32).max(1);
+        let target_height = ((self.bounds.1 * raster_scale).ceil() as u32).max(1);
 
-        if self.centered_origin {
-            screen_space_pos.x -= self.bounds.0 / 2.0;
-            screen_space_pos.y -= self.bounds.1 / 2.0;
+        let render_info = self.render_info.as_mut().unwrap();
+
+        if render_info.target.size.width != target_width
+            || render_info.target.size.height != target_height
+        {
+            render_info.target = Texture::create_render_target(window, target_width, target_height);
+            self._dirty_raster = true;
+        }
+
+        if !self._dirty_raster {
+            return Ok(());
         }
 
+        let text_buffer = self.text_buffer.as_ref().unwrap();
+
+        // Offset the text block inside the bounds for vertical alignment
+        let content_height = text_buffer
+            .layout_runs()
+            .last()
+            .map(|run| run.line_top + self.line_height)
+            .unwrap_or(0.0);
+
+        let top = match self.vertical_align {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Middle => (self.bounds.1 - content_height) / 2.0,
+            VerticalAlign::Bottom => self.bounds.1 - content_height,
+        };
+
         let text_area = glyphon::TextArea {
-            buffer: &self.text_buffer.as_ref().unwrap(),
-            left: screen_space_pos.x,
-            top: screen_space_pos.y,
-            scale: 1.0,
+            buffer: text_buffer,
+            left: 0.0,
+            top: top * raster_scale,
+            scale: raster_scale,
             bounds: glyphon::TextBounds {
                 left: 0,
                 top: 0,
-                right: screen_size.width as i32,
-                bottom: screen_size.height as i32,
+                right: target_width as i32,
+                bottom: target_height as i32,
             },
-            default_color: glyphon::Color::rgb(255, 255, 255),
+            default_color: to_glyphon_color(self.color),
         };
 
-        text_renderer.prepare_with_depth(
+        render_info.text_renderer.prepare(
             &window.device,
             &window.queue,
             font_system,
             text_atlas,
             glyphon::Resolution {
-                width: screen_size.width,
-                height: screen_size.height,
+                width: target_width,
+                height: target_height,
             },
             [text_area],
             swash_cache,
-            |metadata| {
-                // f32::from_bits(metadata as u32)
-                0.2
-            }
         )?;
 
-        text_renderer.render(text_atlas, render_pass)?;
+        // The resolution is stored in the atlas, which is shared by every text.
+        // Submitting right away makes sure this draw uses this text's resolution.
+        let mut encoder = window
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Text Raster Encoder"),
+            });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text Raster Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &render_info.target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_info.text_renderer.render(text_atlas, &mut render_pass)?;
+        }
+        window.queue.submit(std::iter::once(encoder.finish()));
+
+        self._dirty_raster = false;
+
+        Ok(())
+    }
+}
+
+/// Saved form of a `TextSpan`, the font is stored as the path it was loaded from.
+#[derive(Serialize, Deserialize)]
+pub struct TextSpanData {
+    text: String,
+    color: Option<(f32, f32, f32, f32)>,
+    font: Option<PathBuf>,
+    weight: Option<u16>,
+    italic: Option<bool>,
+}
+
+/// Saved form of a `Text`, fonts are stored as the paths they were loaded from.
+#[derive(Serialize, Deserialize)]
+pub struct TextData {
+    text: String,
+    spans: Option<Vec<TextSpanData>>,
+    bounds: (f32, f32),
+    font_size: f32,
+    line_height: f32,
+    advanced_text: bool,
+    centered_origin: bool,
+    pixels_per_unit: f32,
+
+    color: (f32, f32, f32, f32),
+    font: Option<PathBuf>,
+    weight: u16,
+    italic: bool,
+    align: TextAlign,
+    vertical_align: VerticalAlign,
+    wrap: TextWrap,
+}
+
+impl SceneComponent for Text {
+    type Data = TextData;
+
+    fn save(&self, context: &SceneSaveContext) -> anyhow::Result<TextData> {
+        let spans = match &self.spans {
+            Some(spans) => Some(
+                spans
+                    .iter()
+                    .map(|span| {
+                        Ok(TextSpanData {
+                            text: span.text.clone(),
+                            color: span.color,
+                            font: span.font.as_ref().map(|f| context.asset_path(f)).transpose()?,
+                            weight: span.weight,
+                            italic: span.italic,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            ),
+            None => None,
+        };
+
+        Ok(TextData {
+            text: self.text.clone(),
+            spans,
+            bounds: self.bounds,
+            font_size: self.font_size,
+            line_height: self.line_height,
+            advanced_text: self.advanced_text,
+            centered_origin: self.centered_origin,
+            pixels_per_unit: self.pixels_per_unit,
+
+            color: self.color,
+            font: self.font.as_ref().map(|f| context.asset_path(f)).transpose()?,
+            weight: self.weight,
+            italic: self.italic,
+            align: self.align,
+            vertical_align: self.vertical_align,
+            wrap: self.wrap,
+        })
+    }
+
+    fn restore(data: TextData, context: &mut SceneLoadContext) -> anyhow::Result<Self> {
+        let mut text = Text::new(data.text, data.bounds, data.font_size, data.line_height);
+
+        if let Some(spans) = data.spans {
+            let mut restored = Vec::with_capacity(spans.len());
+
+            for span in spans {
+                restored.push(TextSpan {
+                    text: span.text,
+                    color: span.color,
+                    font: span.font.map(|f| context.load_asset::<Font>(&f)).transpose()?,
+                    weight: span.weight,
+                    italic: span.italic,
+                });
+            }
+
+            text.set_rich_text(restored);
+        }
+
+        text.set_advanced_text_shaping(data.advanced_text);
+        text.set_centered_origin(data.centered_origin);
+        text.set_pixels_per_unit(data.pixels_per_unit);
+
+        text.set_color(data.color);
+        text.set_font(data.font.map(|f| context.load_asset::<Font>(&f)).transpose()?);
+        text.set_weight(data.weight);
+        text.set_italic(data.italic);
+        text.set_align(data.align);
+        text.set_vertical_align(data.vertical_align);
+        text.set_wrap(data.wrap);
+
+        Ok(text)
+    }
+}
+
+impl<'a> Renderable<'a> for Text {
+    fn render(
+        &'a mut self,
+        window: &mut crate::window::Window,
+        camera: &'a Camera,
+        transform: &'a mut Transform,
+        render_pass: &mut wgpu::RenderPass<'a>,
+    ) -> anyhow::Result<()> {
+        // Not prepared yet, nothing to draw
+        let render_info = if let Some(render_info) = &self.render_info {
+            render_info
+        } else {
+            return Ok(());
+        };
+
+        render_pass.set_vertex_buffer(0, render_info.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(
+            render_info.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+
+        render_pass.set_bind_group(0, &render_info.target.bind_group, &[]);
+        render_pass.set_bind_group(1, &camera.bind_group, &[]);
+
+        // The quad is sized from the bounds, so the matrix is synced every frame
+        // instead of only when the transform changes.
+        transform.recalculate_matrix();
+
+        let size = Mat4::from_nonuniform_scale(Vec3::new(
+            self.bounds.0 / self.pixels_per_unit,
+            self.bounds.1 / self.pixels_per_unit,
+            1.0,
+        ));
+
+        let local = if self.centered_origin {
+            size
+        } else {
+            // Move the top left corner of the quad to the origin
+            size * Mat4::from_translation(Vec3::new(0.5, -0.5, 0.0))
+        };
+
+        render_info
+            .model_matrix_uniform
+            .update(&(*transform.model_matrix() * local), &window.queue);
+
+        render_pass.set_bind_group(2, &render_info.model_matrix_uniform.bind_group, &[]);
+        render_pass.set_bind_group(3, &render_info.sprite_uniform.bind_group, &[]);
+
+        render_pass.draw_indexed(0..RECT_INDICES.len() as u32, 0, 0..1);
 
-        // Last z
-        self.last_z = transform.position().z;
         Ok(())
     }
+
+    fn type_id() -> std::any::TypeId {
+        std::any::TypeId::of::<Self>()
+    }
+
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let built_in;
+        let shader = match shader {
+            Some(shader) => shader,
+            None => {
+                built_in = window
+                    .device
+                    .create_shader_module(wgpu::include_wgsl!("shaders/sprite.wgsl"));
+                &built_in
+            }
+        };
+
+        let texture_bind_group_layout = Texture::get_bind_group_layout(&window.device);
+        let camera_bind_group_layout = Camera::get_bind_group_layout(&window.device);
+        let model_matrix_bind_group_layout = Uniform::<Mat4>::get_bind_group_layout(&window.device, wgpu::ShaderStages::VERTEX);
+        let sprite_bind_group_layout = Uniform::<SpriteUniform>::get_bind_group_layout(&window.device, wgpu::ShaderStages::FRAGMENT);
+
+        let render_pipeline_layout =
+            window
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("Text Render Pipeline Layout"),
+                    bind_group_layouts: &[
+                        &texture_bind_group_layout,
+                        &camera_bind_group_layout,
+                        &model_matrix_bind_group_layout,
+                        &sprite_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
+        let render_pipeline =
+            window
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Text Render Pipeline"),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: shader,
+                        entry_point: "vs_main",
+                        buffers: &[UvVertex::desc()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: window.config.format,
+                            // The shader un-premultiplies the texture, so it blends like a sprite
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                        // or Features::POLYGON_MODE_POINT
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        // Requires Features::DEPTH_CLIP_CONTROL
+                        unclipped_depth: false,
+                        // Requires Features::CONSERVATIVE_RASTERIZATION
+                        conservative: false,
+                    },
+                    // Glyph edges are translucent, so like translucent sprites,
+                    // text is depth tested but doesn't write depth
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: Renderer2D::DEPTH_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: 1,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    // If the pipeline will be used with a multiview render pass, this
+                    // indicates how many array layers the attachments will have.
+                    multiview: None,
+                });
+
+        Ok(render_pipeline)
+    }
+}
+
+fn to_glyphon_color(color: (f32, f32, f32, f32)) -> glyphon::Color {
+    glyphon::Color::rgba(
+        (color.0.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.1.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.2.clamp(0.0, 1.0) * 255.0) as u8,
+        (color.3.clamp(0.0, 1.0) * 255.0) as u8,
+    )
+}
+
+fn text_attrs<'b>(
+    color: (f32, f32, f32, f32),
+    font: Option<&'b Asset<Font>>,
+    weight: u16,
+    italic: bool,
+) -> glyphon::Attrs<'b> {
+    let mut attrs = glyphon::Attrs::new()
+        .color(to_glyphon_color(color))
+        .weight(glyphon::Weight(weight))
+        .style(if italic {
+            glyphon::Style::Italic
+        } else {
+            glyphon::Style::Normal
+        });
+
+    if let Some(font) = font {
+        attrs = attrs.family(glyphon::Family::Name(font.family()));
+    }
+
+    attrs
 }
@@ -5,7 +5,7 @@ use crate::{
     assets::Asset, camera::Camera, texture::Texture, window::Window, App, transform::Transform, uniform::Uniform, Renderer2D
 };
 
-use super::{UvVertex, Renderable};
+use super::{Renderable, SpriteUniform, UvVertex, DEFAULT_ALPHA_CUTOFF};
 
 const RECT_VERTICES: &[UvVertex] = &[
     UvVertex {
@@ -35,6 +35,10 @@ pub struct TranslucentSprite {
 
     /// Automatically synced during render
     model_matrix_uniform: Uniform<Mat4>,
+
+    alpha_cutoff: f32,
+    sprite_uniform: Uniform<SpriteUniform>,
+    _dirty_sprite_uniform: bool,
 }
 
 impl TranslucentSprite {
@@ -59,13 +63,35 @@ impl TranslucentSprite {
 
         let transform_uniform = Uniform::<Mat4>::new(&app.window.device, &Mat4::identity(), 0, wgpu::ShaderStages::VERTEX);
 
+        let sprite_uniform = Uniform::<SpriteUniform>::new(
+            &app.window.device,
+            &SpriteUniform::new(DEFAULT_ALPHA_CUTOFF, texture.settings().premultiply_alpha),
+            0,
+            wgpu::ShaderStages::FRAGMENT,
+        );
+
         Self {
             texture: Some(texture),
             vertex_buffer,
             index_buffer,
             model_matrix_uniform: transform_uniform,
+
+            alpha_cutoff: DEFAULT_ALPHA_CUTOFF,
+            sprite_uniform,
+            _dirty_sprite_uniform: false,
         }
     }
+
+    /// Pixels with an alpha below the cutoff are not drawn and don't write depth.
+    /// Defaults to `DEFAULT_ALPHA_CUTOFF`.
+    pub fn set_alpha_cutoff(&mut self, alpha_cutoff: f32) {
+        self.alpha_cutoff = alpha_cutoff;
+        self._dirty_sprite_uniform = true;
+    }
+
+    pub fn alpha_cutoff(&self) -> f32 {
+        self.alpha_cutoff
+    }
 }
 
 impl<'a> Renderable<'a> for TranslucentSprite {
@@ -76,6 +102,13 @@ impl<'a> Renderable<'a> for TranslucentSprite {
         transform: &'a mut Transform,
         render_pass: &mut wgpu::RenderPass<'a>,
     ) -> anyhow::Result<()> {
+        // Pick up hot reloaded textures
+        if let Some(texture) = &mut self.texture {
+            if texture.refresh() {
+                self._dirty_sprite_uniform = true;
+            }
+        }
+
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
 
@@ -91,6 +124,22 @@ impl<'a> Renderable<'a> for TranslucentSprite {
 
         render_pass.set_bind_group(2, &self.model_matrix_uniform.bind_group, &[]);
 
+        if self._dirty_sprite_uniform {
+            let premultiplied = self
+                .texture
+                .as_ref()
+                .map(|texture| texture.settings().premultiply_alpha)
+                .unwrap_or(false);
+
+            self.sprite_uniform.update(
+                &SpriteUniform::new(self.alpha_cutoff, premultiplied),
+                &window.queue,
+            );
+            self._dirty_sprite_uniform = false;
+        }
+
+        render_pass.set_bind_group(3, &self.sprite_uniform.bind_group, &[]);
+
         render_pass.draw_indexed(0..RECT_INDICES.len() as u32, 0, 0..1);
 
         Ok(())
@@ -100,21 +149,32 @@ impl<'a> Renderable<'a> for TranslucentSprite {
         std::any::TypeId::of::<Self>()
     }
 
-    fn create_pipeline(window: &mut Window) -> anyhow::Result<wgpu::RenderPipeline> {
-        let shader = window
-            .device
-            .create_shader_module(wgpu::include_wgsl!("shaders/sprite.wgsl"));
+    fn create_pipeline(
+        window: &mut Window,
+        shader: Option<&wgpu::ShaderModule>,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let built_in;
+        let shader = match shader {
+            Some(shader) => shader,
+            None => {
+                built_in = window
+                    .device
+                    .create_shader_module(wgpu::include_wgsl!("shaders/sprite.wgsl"));
+                &built_in
+            }
+        };
 
         let texture_bind_group_layout = Texture::get_bind_group_layout(&window.device);
         let camera_bind_group_layout = Camera::get_bind_group_layout(&window.device);
         let model_matrix_bind_group_layout = Uniform::<Mat4>::get_bind_group_layout(&window.device, wgpu::ShaderStages::VERTEX);
+        let sprite_bind_group_layout = Uniform::<SpriteUniform>::get_bind_group_layout(&window.device, wgpu::ShaderStages::FRAGMENT);
 
         let render_pipeline_layout =
             window
                 .device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: Some("Quad Render Pipeline Layout"),
-                    bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &model_matrix_bind_group_layout],
+                    bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &model_matrix_bind_group_layout, &sprite_bind_group_layout],
                     push_constant_ranges: &[],
                 });
 
@@ -125,12 +185,12 @@ impl<'a> Renderable<'a> for TranslucentSprite {
                     label: Some("Render Pipeline"),
                     layout: Some(&render_pipeline_layout),
                     vertex: wgpu::VertexState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "vs_main",
                         buffers: &[UvVertex::desc()],
                     },
                     fragment: Some(wgpu::FragmentState {
-                        module: &shader,
+                        module: shader,
                         entry_point: "fs_main",
                         targets: &[Some(wgpu::ColorTargetState {
                             format: window.config.format,
@@ -52,4 +52,36 @@ impl ResourceManager {
             .and_then(|res| res.downcast_mut::<Res<T>>())
             .map(|res| &mut res.value)
     }
+
+    /// Type-erased pointers to resources, used to hand resources to systems running in parallel.
+    /// Every resource is borrowed once, mutably only if its type is in `writes`.
+    pub(crate) fn resource_ptrs(&mut self, reads: &[TypeId], writes: &[TypeId]) -> AHashMap<TypeId, ResourcePtr> {
+        let mut ptrs = AHashMap::new();
+
+        for (type_id, res) in self.resources.iter_mut() {
+            if writes.contains(type_id) {
+                ptrs.insert(*type_id, ResourcePtr::Write(res.as_mut() as *mut dyn Any));
+            } else if reads.contains(type_id) {
+                ptrs.insert(*type_id, ResourcePtr::Read(&**res as *const dyn Any));
+            }
+        }
+
+        ptrs
+    }
+}
+
+/// A pointer returned by `ResourceManager::resource_ptrs`.
+#[derive(Clone, Copy)]
+pub(crate) enum ResourcePtr {
+    Read(*const dyn Any),
+    Write(*mut dyn Any),
+}
+
+/// Gets the value out of a resource returned by `ResourceManager::resource_ptr`.
+pub(crate) fn downcast_resource<T: 'static>(res: &dyn Any) -> Option<&T> {
+    res.downcast_ref::<Res<T>>().map(|res| &res.value)
+}
+
+pub(crate) fn downcast_resource_mut<T: 'static>(res: &mut dyn Any) -> Option<&mut T> {
+    res.downcast_mut::<Res<T>>().map(|res| &mut res.value)
 }
\ No newline at end of file
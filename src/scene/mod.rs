@@ -0,0 +1,492 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use ahash::AHashMap;
+
+use crate::{
+    assets::{AssetManager, LoadGroup},
+    camera,
+    events::{SceneLoaded, SceneUnloaded},
+    renderer::ScreenOverlay,
+    App,
+};
+
+use self::transition::ActiveTransition;
+
+pub mod persistent;
+pub use persistent::Persistent;
+pub mod prefab;
+pub use prefab::{Prefab, PrefabInstance};
+pub mod serialize;
+pub mod transition;
+pub use transition::{Transition, TransitionKind};
+pub use serialize::{
+    ComponentRegistry, SceneComponent, SceneFormat, SceneLoadContext, SceneSaveContext,
+};
+
+
+pub struct Scene {
+    pub world: hecs::World,
+    pub camera: Option<camera::Camera>,
+}
+
+/// A change to the loaded scenes, applied between systems.
+pub(crate) enum SceneChange {
+    Load(String),
+    Push(String),
+    Pop,
+    LoadAdditive(String),
+    UnloadAdditive(String),
+}
+
+/// A scene that is loaded, with the name of its generator.
+struct LoadedScene {
+    name: String,
+    scene: Scene,
+}
+
+pub struct ScenesManager {
+    scenes: AHashMap<String, Rc<SceneGenerator>>,
+    /// Scenes loaded with `load` and `push`, the last one is the current scene.
+    /// Scenes below the top are paused, they are drawn but don't run systems or physics.
+    stack: Vec<LoadedScene>,
+    /// Scenes loaded with `load_additive`, drawn over the stack and always running
+    additive: Vec<LoadedScene>,
+    transition: Option<ActiveTransition>,
+    /// Scene to load once its assets are ready
+    pending_scene: Option<(String, LoadGroup)>,
+    /// Scene changes waiting to be applied, see `apply_changes`
+    changes: Vec<SceneChange>,
+    /// Components that are saved and loaded with scenes
+    registry: ComponentRegistry,
+    prefabs: AHashMap<String, Prefab>,
+}
+
+impl ScenesManager {
+    pub(crate) fn new() -> Self {
+        Self {
+            scenes: AHashMap::new(),
+            stack: Vec::new(),
+            additive: Vec::new(),
+            transition: None,
+            pending_scene: None,
+            changes: Vec::new(),
+            registry: ComponentRegistry::new(),
+            prefabs: AHashMap::new(),
+        }
+    }
+
+    /// Name of the scene on top of the stack.
+    pub fn current_scene_name(&self) -> Option<&String> {
+        self.stack.last().map(|loaded| &loaded.name)
+    }
+
+    /// The scene on top of the stack.
+    pub fn current_scene(&self) -> Option<&Scene> {
+        self.stack.last().map(|loaded| &loaded.scene)
+    }
+
+    pub fn current_scene_mut(&mut self) -> Option<&mut Scene> {
+        self.stack.last_mut().map(|loaded| &mut loaded.scene)
+    }
+
+    /// A loaded scene by name, whether it is on the stack or additive.
+    /// If the same scene is loaded more than once, the top most one is returned.
+    pub fn scene(&self, name: &str) -> Option<&Scene> {
+        self.additive
+            .iter()
+            .rev()
+            .chain(self.stack.iter().rev())
+            .find(|loaded| loaded.name == name)
+            .map(|loaded| &loaded.scene)
+    }
+
+    pub fn scene_mut(&mut self, name: &str) -> Option<&mut Scene> {
+        self.additive
+            .iter_mut()
+            .rev()
+            .chain(self.stack.iter_mut().rev())
+            .find(|loaded| loaded.name == name)
+            .map(|loaded| &mut loaded.scene)
+    }
+
+    /// True if the scene runs its systems this frame, i.e. it is the current scene or additive.
+    pub fn is_active(&self, name: &str) -> bool {
+        self.current_scene_name().map(|current| current == name).unwrap_or(false)
+            || self.additive.iter().any(|loaded| loaded.name == name)
+    }
+
+    /// Every loaded scene in draw order: the stack from the bottom up, then the additive scenes.
+    pub fn loaded_scenes(&self) -> impl Iterator<Item = (&str, &Scene)> {
+        self.stack
+            .iter()
+            .chain(self.additive.iter())
+            .map(|loaded| (loaded.name.as_str(), &loaded.scene))
+    }
+
+    pub(crate) fn loaded_scenes_mut(&mut self) -> impl Iterator<Item = &mut Scene> {
+        self.stack
+            .iter_mut()
+            .chain(self.additive.iter_mut())
+            .map(|loaded| &mut loaded.scene)
+    }
+
+    /// The scenes that run this frame with their names: the current scene, then the additive scenes.
+    pub(crate) fn active_scenes_mut(&mut self) -> impl Iterator<Item = (&str, &mut Scene)> {
+        self.stack
+            .last_mut()
+            .into_iter()
+            .chain(self.additive.iter_mut())
+            .map(|loaded| (loaded.name.as_str(), &mut loaded.scene))
+    }
+
+    /// Queues the scene to replace every scene on the stack.
+    /// Additive scenes stay loaded, and entities marked `Persistent` move into the new scene.
+    pub fn load<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
+        let name = self.check_exists(name.into())?;
+        self.changes.push(SceneChange::Load(name));
+
+        Ok(())
+    }
+
+    /// Queues a scene to load on top of the current one, which is paused until the new scene is popped.
+    /// Use this for pause menus and other overlays that have to keep the game state underneath.
+    pub fn push<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
+        let name = self.check_exists(name.into())?;
+        self.changes.push(SceneChange::Push(name));
+
+        Ok(())
+    }
+
+    /// Queues the current scene to be unloaded, resuming the one below it.
+    pub fn pop(&mut self) {
+        self.changes.push(SceneChange::Pop);
+    }
+
+    /// Queues a scene that runs and is drawn alongside the stack, e.g. a HUD.
+    /// Additive scenes are drawn in the order they were loaded, over the stack.
+    pub fn load_additive<S: Into<String>>(&mut self, name: S) -> anyhow::Result<()> {
+        let name = self.check_exists(name.into())?;
+        self.changes.push(SceneChange::LoadAdditive(name));
+
+        Ok(())
+    }
+
+    /// Queues an additive scene to be unloaded, the one loaded last if it was loaded more than once.
+    pub fn unload_additive<S: Into<String>>(&mut self, name: S) {
+        self.changes.push(SceneChange::UnloadAdditive(name.into()));
+    }
+
+    /// True if scene changes are waiting to be applied.
+    pub fn has_pending_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    fn check_exists(&self, name: String) -> anyhow::Result<String> {
+        if self.scenes.contains_key(&name) {
+            Ok(name)
+        } else {
+            Err(anyhow::anyhow!("Scene '{}' not found.", name))
+        }
+    }
+
+    /// Queues the scene to replace the stack, hidden behind a transition.
+    /// The scene is switched halfway through, when the screen is fully covered.
+    /// Fails before the transition starts if the scene doesn't exist.
+    pub fn load_with_transition<S: Into<String>>(&mut self, name: S, transition: Transition) -> anyhow::Result<()> {
+        let name = self.check_exists(name.into())?;
+        self.start_transition(SceneChange::Load(name), transition);
+
+        Ok(())
+    }
+
+    /// Like `push`, hidden behind a transition.
+    pub fn push_with_transition<S: Into<String>>(&mut self, name: S, transition: Transition) -> anyhow::Result<()> {
+        let name = self.check_exists(name.into())?;
+        self.start_transition(SceneChange::Push(name), transition);
+
+        Ok(())
+    }
+
+    /// Like `pop`, hidden behind a transition.
+    pub fn pop_with_transition(&mut self, transition: Transition) {
+        self.start_transition(SceneChange::Pop, transition);
+    }
+
+    /// True while a transition is playing.
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    /// Starting a transition while another one is playing replaces it,
+    /// the change of the old one is queued right away if it hadn't happened yet.
+    fn start_transition(&mut self, change: SceneChange, transition: Transition) {
+        if let Some(old) = self.transition.take() {
+            if let Some(change) = old.change {
+                self.changes.push(change);
+            }
+        }
+
+        self.transition = Some(ActiveTransition::new(change, transition));
+    }
+
+    /// Advances the transition, queueing its scene change once the screen is covered.
+    pub(crate) fn update_transition(&mut self) {
+        let Some(transition) = &mut self.transition else {
+            return;
+        };
+
+        let progress = transition.progress();
+
+        if progress >= 0.5 {
+            if let Some(change) = transition.change.take() {
+                self.changes.push(change);
+            }
+        }
+
+        if progress >= 1.0 {
+            self.transition = None;
+        }
+    }
+
+    /// Applies the queued scene changes in order.
+    /// Scene hooks get the whole app, so this can't borrow the manager while they run.
+    /// Changes queued by the hooks themselves are applied by the next call.
+    pub(crate) fn apply_changes(app: &mut App) {
+        let changes = std::mem::take(&mut app.scenes.changes);
+
+        for change in changes {
+            if let Err(e) = Self::apply(app, change) {
+                log::error!("Failed to change scene: {}", e);
+            }
+        }
+    }
+
+    fn apply(app: &mut App, change: SceneChange) -> anyhow::Result<()> {
+        match change {
+            SceneChange::Load(name) => {
+                // Look up first so a missing scene doesn't leave the stack empty
+                let scene_gen = Self::generator(app, &name)?;
+
+                // Persistent entities are moved out before the old scenes are unloaded
+                let mut carried = hecs::World::new();
+                let mut carried_roots = Vec::new();
+                for loaded in &mut app.scenes.stack {
+                    carried_roots.extend(persistent::take_persistent(&mut loaded.scene.world, &mut carried));
+                }
+
+                while let Some(loaded) = app.scenes.stack.pop() {
+                    Self::unload(app, loaded);
+                }
+
+                let mut scene = scene_gen.generate(app);
+                let arrived = persistent::move_entities(&mut carried, &mut scene.world, &carried_roots);
+                app.events.send(SceneLoaded { name: name.clone() });
+                app.scenes.stack.push(LoadedScene { name, scene });
+
+                persistent::arrive(app, &arrived);
+            }
+            SceneChange::Push(name) => {
+                let scene = Self::generator(app, &name)?.generate(app);
+                app.events.send(SceneLoaded { name: name.clone() });
+                app.scenes.stack.push(LoadedScene { name, scene });
+            }
+            SceneChange::Pop => {
+                let loaded = app
+                    .scenes
+                    .stack
+                    .pop()
+                    .ok_or(anyhow::anyhow!("No scene to pop."))?;
+
+                Self::unload(app, loaded);
+            }
+            SceneChange::LoadAdditive(name) => {
+                let scene = Self::generator(app, &name)?.generate(app);
+                app.events.send(SceneLoaded { name: name.clone() });
+                app.scenes.additive.push(LoadedScene { name, scene });
+            }
+            SceneChange::UnloadAdditive(name) => {
+                let index = app
+                    .scenes
+                    .additive
+                    .iter()
+                    .rposition(|loaded| loaded.name == name)
+                    .ok_or(anyhow::anyhow!("Additive scene '{}' is not loaded.", name))?;
+
+                let loaded = app.scenes.additive.remove(index);
+                Self::unload(app, loaded);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The generator is shared so it can be called while the app is borrowed mutably.
+    fn generator(app: &App, name: &str) -> anyhow::Result<Rc<SceneGenerator>> {
+        app.scenes
+            .scenes
+            .get(name)
+            .cloned()
+            .ok_or(anyhow::anyhow!("Scene '{}' not found.", name))
+    }
+
+    /// Runs the `on_unload` hook of a scene that was removed.
+    fn unload(app: &mut App, mut loaded: LoadedScene) {
+        if let Ok(scene_gen) = Self::generator(app, &loaded.name) {
+            scene_gen.unload(&mut loaded.scene, app);
+        }
+
+        app.events.send(SceneUnloaded { name: loaded.name });
+    }
+
+    /// Unloads every scene right away, running their `on_unload` hooks.
+    pub(crate) fn unload_all(app: &mut App) {
+        while let Some(loaded) = app.scenes.additive.pop() {
+            Self::unload(app, loaded);
+        }
+
+        while let Some(loaded) = app.scenes.stack.pop() {
+            Self::unload(app, loaded);
+        }
+    }
+
+    /// What the transition draws over the scenes this frame.
+    pub(crate) fn transition_overlay(&self) -> Option<ScreenOverlay> {
+        self.transition.as_ref().map(|transition| transition.overlay())
+    }
+
+    /// Loads the scene once every asset in the group has finished loading.
+    /// The current scene keeps running until then, so it can show a loading screen
+    /// using `AssetManager::progress`. Replaces any scene that is already waiting.
+    pub fn load_when_ready<S: Into<String>>(&mut self, name: S, group: LoadGroup) {
+        self.pending_scene = Some((name.into(), group));
+    }
+
+    /// Name of the scene waiting for its assets, if any.
+    pub fn pending_scene_name(&self) -> Option<&String> {
+        self.pending_scene.as_ref().map(|(name, _)| name)
+    }
+
+    /// Takes the pending scene if its assets are done loading.
+    /// Failed loads don't block the scene, they are logged by the asset manager.
+    pub(crate) fn take_ready_scene(&mut self, assets: &AssetManager) -> Option<String> {
+        let (_, group) = self.pending_scene.as_ref()?;
+
+        if assets.group_state(group).is_loading() {
+            return None;
+        }
+
+        self.pending_scene.take().map(|(name, _)| name)
+    }
+
+    pub fn add<S: Into<String>>(&mut self, name: S, scene_gen: SceneGenerator) {
+        self.scenes.insert(name.into(), Rc::new(scene_gen));
+    }
+
+    /// The components used by `Scene::save` and `Scene::load`.
+    pub fn registry(&self) -> &ComponentRegistry {
+        &self.registry
+    }
+
+    /// Register user components here so they are saved with scenes.
+    pub fn registry_mut(&mut self) -> &mut ComponentRegistry {
+        &mut self.registry
+    }
+
+    /// Registers a prefab under a name, replacing any prefab with the same name.
+    pub fn add_prefab<S: Into<String>>(&mut self, name: S, prefab: Prefab) {
+        self.prefabs.insert(name.into(), prefab);
+    }
+
+    /// Loads a prefab file relative to the asset root and registers it under a name.
+    pub fn load_prefab<S: Into<String>, P: AsRef<Path>>(
+        &mut self,
+        name: S,
+        path: P,
+        assets: &AssetManager,
+    ) -> anyhow::Result<()> {
+        self.add_prefab(name, Prefab::load(path, assets)?);
+
+        Ok(())
+    }
+
+    pub fn prefab(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn remove_prefab(&mut self, name: &str) -> Option<Prefab> {
+        self.prefabs.remove(name)
+    }
+}
+
+
+/// This struct allows the scene manager to load a scene.
+/// It stores a function that is called when the scene is loaded.
+pub struct SceneGenerator {
+    pub(crate) on_load: Option<Box<dyn Fn(&mut Scene, &mut App)>>,
+    pub(crate) on_unload: Option<Box<dyn Fn(&mut Scene, &mut App)>>,
+}
+
+impl SceneGenerator {
+    /// This function is called every time the scene is loaded.
+    /// When this is called, the world and camera will be empty.
+    /// Fill the world with entities and add a camera to the scene.
+    pub fn on_load(&mut self, f: impl Fn(&mut Scene, &mut App) + 'static) {
+        self.on_load = Some(Box::new(f));
+    }
+
+    /// This function is called every time the scene is unloaded, before its world is dropped.
+    /// Use it to save state or release resources that were created in `on_load`.
+    pub fn on_unload(&mut self, f: impl Fn(&mut Scene, &mut App) + 'static) {
+        self.on_unload = Some(Box::new(f));
+    }
+
+    /// Builder form of `on_unload`.
+    pub fn with_on_unload(mut self, f: impl Fn(&mut Scene, &mut App) + 'static) -> Self {
+        self.on_unload(f);
+        self
+    }
+
+    /// Create a new scene generator.
+    /// f is called when the scene is loaded.
+    pub fn new(f: impl Fn(&mut Scene, &mut App) + 'static) -> Self {
+        Self {
+            on_load: Some(Box::new(f)),
+            on_unload: None,
+        }
+    }
+
+    /// Create a scene generator that loads a scene saved with `Scene::save`.
+    /// The path is relative to the asset root.
+    pub fn from_file<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+
+        Self::new(move |scene, app| {
+            match Scene::load(&path, &app.scenes.registry, &mut app.assets, &app.window) {
+                Ok(loaded) => *scene = loaded,
+                Err(e) => log::error!("{:#}", e),
+            }
+        })
+    }
+
+    pub(crate) fn generate(&self, app: &mut App) -> Scene {
+        let mut scene = Scene {
+            world: hecs::World::new(),
+            camera: None,
+        };
+
+        if let Some(f) = &self.on_load {
+            f(&mut scene, app);
+        }
+
+        scene
+    }
+
+    pub(crate) fn unload(&self, scene: &mut Scene, app: &mut App) {
+        if let Some(f) = &self.on_unload {
+            f(scene, app);
+        }
+    }
+}
\ No newline at end of file
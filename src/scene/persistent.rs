@@ -0,0 +1,118 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use hecs::Entity;
+
+use crate::{
+    hierarchy::{self, Children, Parent},
+    App,
+};
+
+type ArriveHook = Arc<dyn Fn(&mut App, Entity) + Send + Sync>;
+
+/// Marks an entity that survives `ScenesManager::load`.
+/// It is moved into the new scene together with its children, after the scene's `on_load` has run.
+/// Entities get new ids when they move, use `with_on_arrive` to find them again.
+/// Scenes pushed on top of the stack don't take persistent entities from the scene below.
+#[derive(Clone, Default)]
+pub struct Persistent {
+    on_arrive: Option<ArriveHook>,
+}
+
+impl Persistent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Called with the new id of the entity every time it arrives in a new scene.
+    /// The scene it arrived in is the current scene.
+    pub fn with_on_arrive(mut self, f: impl Fn(&mut App, Entity) + Send + Sync + 'static) -> Self {
+        self.on_arrive = Some(Arc::new(f));
+        self
+    }
+}
+
+/// Moves the persistent entities of a world into `carried`, returning their ids in it.
+/// Persistent entities attached to an entity that isn't persistent are detached first,
+/// keeping their position in the world.
+pub(crate) fn take_persistent(world: &mut hecs::World, carried: &mut hecs::World) -> Vec<Entity> {
+    let marked: Vec<Entity> = world
+        .query::<&Persistent>()
+        .iter()
+        .map(|(entity, _)| entity)
+        .collect();
+
+    // Children of persistent entities move with their parent
+    let roots: Vec<Entity> = marked
+        .iter()
+        .copied()
+        .filter(|&entity| {
+            !marked
+                .iter()
+                .any(|&other| other != entity && hierarchy::is_ancestor(world, other, entity))
+        })
+        .collect();
+
+    for &root in &roots {
+        if let Err(e) = hierarchy::set_parent_keep_world(world, root, None) {
+            log::error!("Failed to detach persistent entity: {}", e);
+        }
+    }
+
+    move_entities(world, carried, &roots)
+}
+
+/// Moves the entities and their descendants to another world, returning the new ids of all of them.
+/// `Parent` and `Children` are remapped to the new ids, so the roots must not have a parent.
+pub(crate) fn move_entities(from: &mut hecs::World, to: &mut hecs::World, roots: &[Entity]) -> Vec<Entity> {
+    // Collect the whole tree first, children can't be read once their parent is taken
+    let mut order = Vec::new();
+    let mut pending: Vec<Entity> = roots.to_vec();
+    while let Some(entity) = pending.pop() {
+        order.push(entity);
+        pending.extend(hierarchy::children(from, entity));
+    }
+
+    let mut ids = AHashMap::new();
+    let mut moved = Vec::new();
+    for entity in order {
+        if let Ok(taken) = from.take(entity) {
+            let new_id = to.spawn(taken);
+            ids.insert(entity, new_id);
+            moved.push(new_id);
+        }
+    }
+
+    for &entity in &moved {
+        if let Ok(mut parent) = to.get::<&mut Parent>(entity) {
+            if let Some(&new_parent) = ids.get(&parent.0) {
+                parent.0 = new_parent;
+            }
+        }
+
+        if let Ok(mut children) = to.get::<&mut Children>(entity) {
+            children.0 = children
+                .0
+                .iter()
+                .filter_map(|child| ids.get(child).copied())
+                .collect();
+        }
+    }
+
+    moved
+}
+
+/// Runs the `on_arrive` hooks of entities that were moved into the current scene.
+pub(crate) fn arrive(app: &mut App, entities: &[Entity]) {
+    for &entity in entities {
+        let hook = app
+            .scenes
+            .current_scene()
+            .and_then(|scene| scene.world.get::<&Persistent>(entity).ok())
+            .and_then(|persistent| persistent.on_arrive.clone());
+
+        if let Some(hook) = hook {
+            hook(app, entity);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+use std::{any::TypeId, fmt, path::Path};
+
+use bincode::Options;
+use hecs::{Entity, EntityBuilder};
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeStruct},
+    Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    assets::AssetManager,
+    hierarchy::{self, Children, Parent},
+    window::Window,
+};
+
+use super::serialize::{
+    ComponentRegistry, ComponentsRef, ComponentsSeed, SceneFormat, SceneLoadContext,
+    SceneSaveContext,
+};
+
+/// Bumped whenever the layout of prefab files changes.
+const PREFAB_VERSION: u32 = 1;
+
+const PREFAB_FIELDS: &[&str] = &["version", "root"];
+const NODE_FIELDS: &[&str] = &["components", "children"];
+
+/// A reusable template of an entity and its children.
+/// Prefabs are kept encoded and decoded through the `ComponentRegistry` every time they are spawned,
+/// so each instance gets its own components and its assets are loaded when it is spawned.
+///
+/// A RON prefab file looks like this:
+/// ```ron
+/// (
+///     version: 1,
+///     root: (
+///         components: {
+///             "Transform": (...),
+///             "Rect": (color: (1.0, 1.0, 1.0, 1.0)),
+///         },
+///         children: [],
+///     ),
+/// )
+/// ```
+#[derive(Clone)]
+pub struct Prefab {
+    format: SceneFormat,
+    bytes: Vec<u8>,
+}
+
+/// Entities created by `Prefab::spawn`.
+#[derive(Debug, Clone)]
+pub struct PrefabInstance {
+    pub root: Entity,
+    /// Every entity spawned for the children of the prefab, depth first.
+    /// They are attached to their parents, see `hierarchy`.
+    pub children: Vec<Entity>,
+}
+
+impl Prefab {
+    /// Wraps an encoded prefab, it is only decoded when it is spawned.
+    pub fn from_bytes(bytes: Vec<u8>, format: SceneFormat) -> Self {
+        Self { format, bytes }
+    }
+
+    /// Loads a prefab file relative to the asset root, from the mounted archive if it is in there.
+    /// The format is picked from the extension, see `SceneFormat::from_path`.
+    pub fn load<P: AsRef<Path>>(path: P, assets: &AssetManager) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = assets.read_file(path)?;
+
+        Ok(Self::from_bytes(bytes, SceneFormat::from_path(path)))
+    }
+
+    /// Creates a prefab from the registered components of an entity and its children.
+    /// Assets are stored as the paths they were loaded from.
+    pub fn from_entity(
+        world: &hecs::World,
+        entity: Entity,
+        registry: &ComponentRegistry,
+        assets: &AssetManager,
+    ) -> anyhow::Result<Self> {
+        let bytes = Self::encode(world, entity, SceneFormat::Binary, registry, assets)?;
+
+        Ok(Self::from_bytes(bytes, SceneFormat::Binary))
+    }
+
+    /// Encodes an entity as a prefab file, e.g. to write it to a RON file that can be edited by hand.
+    pub fn encode(
+        world: &hecs::World,
+        entity: Entity,
+        format: SceneFormat,
+        registry: &ComponentRegistry,
+        assets: &AssetManager,
+    ) -> anyhow::Result<Vec<u8>> {
+        let context = SceneSaveContext::new(assets);
+
+        if !world.contains(entity) {
+            return Err(anyhow::anyhow!("Entity {:?} doesn't exist.", entity));
+        }
+
+        let prefab = PrefabRef {
+            root: NodeRef {
+                world,
+                entity,
+                registry,
+                context: &context,
+            },
+        };
+
+        let bytes = match format {
+            SceneFormat::Ron => {
+                ron::ser::to_string_pretty(&prefab, ron::ser::PrettyConfig::default())?.into_bytes()
+            }
+            SceneFormat::Binary => bincode::DefaultOptions::new().serialize(&prefab)?,
+        };
+
+        Ok(bytes)
+    }
+
+    /// Creates a prefab from a bundle of components, which all have to be registered.
+    pub fn from_components<B: hecs::DynamicBundle>(
+        components: B,
+        registry: &ComponentRegistry,
+        assets: &AssetManager,
+    ) -> anyhow::Result<Self> {
+        let mut world = hecs::World::new();
+        let entity = world.spawn(components);
+
+        if let Some(type_id) = world
+            .entity(entity)?
+            .component_types()
+            .find(|type_id| !registry.is_registered_id(*type_id))
+        {
+            return Err(anyhow::anyhow!(
+                "Prefab component {:?} is not registered, it would be left out.",
+                type_id
+            ));
+        }
+
+        Self::from_entity(&world, entity, registry, assets)
+    }
+
+    pub fn format(&self) -> SceneFormat {
+        self.format
+    }
+
+    /// The encoded prefab, in `Prefab::format`.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Spawns the prefab into the world.
+    pub fn spawn(
+        &self,
+        world: &mut hecs::World,
+        registry: &ComponentRegistry,
+        assets: &mut AssetManager,
+        window: &Window,
+    ) -> anyhow::Result<PrefabInstance> {
+        self.spawn_with(world, registry, assets, window, |_| {})
+    }
+
+    /// Spawns the prefab into the world, with per instance overrides for the root entity.
+    /// Components added to the builder replace the ones from the prefab, e.g. a different `Transform`.
+    pub fn spawn_with(
+        &self,
+        world: &mut hecs::World,
+        registry: &ComponentRegistry,
+        assets: &mut AssetManager,
+        window: &Window,
+        overrides: impl FnOnce(&mut EntityBuilder),
+    ) -> anyhow::Result<PrefabInstance> {
+        let mut context = SceneLoadContext::new(assets, window);
+        let mut spawned = Vec::new();
+        let mut overrides = Some(overrides);
+
+        let seed = PrefabSeed {
+            registry,
+            context: &mut context,
+            world: &mut *world,
+            spawned: &mut spawned,
+            overrides: &mut overrides,
+        };
+
+        let result = match self.format {
+            SceneFormat::Ron => ron::Deserializer::from_bytes(&self.bytes)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut deserializer| {
+                    seed.deserialize(&mut deserializer)?;
+                    deserializer.end()?;
+                    Ok(())
+                }),
+            SceneFormat::Binary => bincode::DefaultOptions::new()
+                .deserialize_seed(seed, &self.bytes)
+                .map_err(anyhow::Error::from),
+        };
+
+        // Don't leave half a prefab behind
+        if let Err(e) = result {
+            for entity in spawned {
+                let _ = world.despawn(entity);
+            }
+
+            return Err(anyhow::anyhow!("Failed to spawn prefab: {:#}", e));
+        }
+
+        let root = spawned[0];
+
+        Ok(PrefabInstance {
+            root,
+            children: spawned.split_off(1),
+        })
+    }
+}
+
+struct PrefabRef<'a> {
+    root: NodeRef<'a>,
+}
+
+impl Serialize for PrefabRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Prefab", PREFAB_FIELDS.len())?;
+        state.serialize_field("version", &PREFAB_VERSION)?;
+        state.serialize_field("root", &self.root)?;
+        state.end()
+    }
+}
+
+/// The hierarchy is stored by nesting, the entity ids in `Parent` and `Children` mean nothing in other worlds.
+fn hierarchy_types() -> [TypeId; 2] {
+    [TypeId::of::<Parent>(), TypeId::of::<Children>()]
+}
+
+struct NodeRef<'a> {
+    world: &'a hecs::World,
+    entity: Entity,
+    registry: &'a ComponentRegistry,
+    context: &'a SceneSaveContext<'a>,
+}
+
+impl Serialize for NodeRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entity = self
+            .world
+            .entity(self.entity)
+            .map_err(|_| {
+                <S::Error as ser::Error>::custom(format!("Entity {:?} doesn't exist.", self.entity))
+            })?;
+
+        let children: Vec<NodeRef> = hierarchy::children(self.world, self.entity)
+            .into_iter()
+            .map(|child| NodeRef {
+                world: self.world,
+                entity: child,
+                registry: self.registry,
+                context: self.context,
+            })
+            .collect();
+
+        let mut state = serializer.serialize_struct("PrefabNode", NODE_FIELDS.len())?;
+        state.serialize_field(
+            "components",
+            &ComponentsRef {
+                registry: self.registry,
+                entity,
+                context: self.context,
+                exclude: &hierarchy_types(),
+            },
+        )?;
+        state.serialize_field("children", &children)?;
+        state.end()
+    }
+}
+
+struct PrefabSeed<'a, 'b, F> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+    world: &'a mut hecs::World,
+    spawned: &'a mut Vec<Entity>,
+    overrides: &'a mut Option<F>,
+}
+
+impl<F: FnOnce(&mut EntityBuilder)> PrefabSeed<'_, '_, F> {
+    fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+        if version != PREFAB_VERSION {
+            return Err(E::custom(format!(
+                "Prefab has version {}, only version {} is supported.",
+                version, PREFAB_VERSION
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn root_seed(&mut self) -> NodeSeed<'_, '_> {
+        NodeSeed {
+            registry: self.registry,
+            context: &mut *self.context,
+            world: &mut *self.world,
+            spawned: &mut *self.spawned,
+            overrides: Some(&mut *self.overrides as &mut dyn OverrideRoot),
+            parent: None,
+        }
+    }
+}
+
+impl<'de, F: FnOnce(&mut EntityBuilder)> DeserializeSeed<'de> for PrefabSeed<'_, '_, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_struct("Prefab", PREFAB_FIELDS, self)
+    }
+}
+
+impl<'de, F: FnOnce(&mut EntityBuilder)> Visitor<'de> for PrefabSeed<'_, '_, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefab")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let version: u32 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &"a prefab"))?;
+        Self::check_version::<A::Error>(version)?;
+
+        seq.next_element_seed(self.root_seed())?
+            .ok_or_else(|| de::Error::invalid_length(1, &"a prefab"))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut version = None;
+        let mut root = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => version = Some(map.next_value::<u32>()?),
+                "root" => {
+                    let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+                    Self::check_version::<A::Error>(version)?;
+
+                    map.next_value_seed(self.root_seed())?;
+                    root = true;
+                }
+                _ => return Err(de::Error::unknown_field(&key, PREFAB_FIELDS)),
+            }
+        }
+
+        if !root {
+            return Err(de::Error::missing_field("root"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Spawns one entity of the prefab and then its children.
+struct NodeSeed<'a, 'b> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+    world: &'a mut hecs::World,
+    spawned: &'a mut Vec<Entity>,
+    /// Only set for the root
+    overrides: Option<&'a mut dyn OverrideRoot>,
+    /// None for the root
+    parent: Option<Entity>,
+}
+
+/// Lets nodes take the root overrides without knowing the closure type.
+trait OverrideRoot {
+    fn apply(&mut self, entity: &mut EntityBuilder);
+}
+
+impl<F: FnOnce(&mut EntityBuilder)> OverrideRoot for Option<F> {
+    fn apply(&mut self, entity: &mut EntityBuilder) {
+        if let Some(overrides) = self.take() {
+            overrides(entity);
+        }
+    }
+}
+
+impl NodeSeed<'_, '_> {
+    fn child_seed(&mut self, parent: Entity) -> ChildrenSeed<'_, '_> {
+        ChildrenSeed {
+            registry: self.registry,
+            context: &mut *self.context,
+            world: &mut *self.world,
+            spawned: &mut *self.spawned,
+            parent,
+        }
+    }
+
+    /// Spawns the entity once its components are read, before its children so they can be attached to it.
+    fn spawn<E: de::Error>(&mut self, mut entity: EntityBuilder) -> Result<Entity, E> {
+        if let Some(overrides) = self.overrides.take() {
+            overrides.apply(&mut entity);
+        }
+
+        let entity = self.world.spawn(entity.build());
+        self.spawned.push(entity);
+
+        if let Some(parent) = self.parent {
+            hierarchy::set_parent(self.world, entity, Some(parent)).map_err(E::custom)?;
+        }
+
+        Ok(entity)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for NodeSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_struct("PrefabNode", NODE_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for NodeSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a prefab entity")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut entity = EntityBuilder::new();
+
+        seq.next_element_seed(ComponentsSeed {
+            registry: self.registry,
+            entity: &mut entity,
+            context: &mut *self.context,
+        })?
+        .ok_or_else(|| de::Error::invalid_length(0, &"a prefab entity"))?;
+
+        let entity = self.spawn::<A::Error>(entity)?;
+
+        seq.next_element_seed(self.child_seed(entity))?
+            .ok_or_else(|| de::Error::invalid_length(1, &"a prefab entity"))
+    }
+
+    // Children are only read after the components, so the parent always exists when they are spawned
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut entity = Some(EntityBuilder::new());
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "components" => {
+                    let builder = entity
+                        .as_mut()
+                        .ok_or_else(|| de::Error::custom("Components have to come before children."))?;
+
+                    map.next_value_seed(ComponentsSeed {
+                        registry: self.registry,
+                        entity: builder,
+                        context: &mut *self.context,
+                    })?;
+                }
+                "children" => {
+                    let builder = entity
+                        .take()
+                        .ok_or_else(|| de::Error::duplicate_field("children"))?;
+                    let entity = self.spawn::<A::Error>(builder)?;
+
+                    map.next_value_seed(self.child_seed(entity))?;
+                }
+                _ => return Err(de::Error::unknown_field(&key, NODE_FIELDS)),
+            }
+        }
+
+        // Children are optional
+        if let Some(builder) = entity {
+            self.spawn::<A::Error>(builder)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct ChildrenSeed<'a, 'b> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+    world: &'a mut hecs::World,
+    spawned: &'a mut Vec<Entity>,
+    parent: Entity,
+}
+
+impl<'de> DeserializeSeed<'de> for ChildrenSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ChildrenSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of prefab entities")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(NodeSeed {
+                registry: self.registry,
+                context: &mut *self.context,
+                world: &mut *self.world,
+                spawned: &mut *self.spawned,
+                overrides: None,
+                parent: Some(self.parent),
+            })?
+            .is_some()
+        {}
+
+        Ok(())
+    }
+}
@@ -0,0 +1,739 @@
+use std::{
+    any::TypeId,
+    fmt,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use bincode::Options;
+use hecs::{
+    serialize::row::{DeserializeContext, SerializeContext},
+    EntityBuilder, EntityRef,
+};
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeStruct},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    assets::{Asset, AssetManager},
+    camera::{Camera, Projection},
+    hierarchy::{Children, Parent},
+    transform::Transform,
+    window::Window,
+};
+
+use super::Scene;
+
+/// Bumped whenever the layout of scene files changes.
+const SCENE_VERSION: u32 = 1;
+
+const SCENE_FIELDS: &[&str] = &["version", "camera", "world"];
+
+/// Encoding of a saved scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    /// Human readable, for levels that are edited by hand or kept in version control
+    Ron,
+    /// Compact and fast to load, for save games
+    Binary,
+}
+
+impl SceneFormat {
+    /// Picks the format from the file extension, `.ron` is RON and anything else is binary.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("ron") => SceneFormat::Ron,
+            _ => SceneFormat::Binary,
+        }
+    }
+}
+
+/// Passed to `SceneComponent::save`.
+pub struct SceneSaveContext<'a> {
+    assets: &'a AssetManager,
+}
+
+impl<'a> SceneSaveContext<'a> {
+    pub(crate) fn new(assets: &'a AssetManager) -> Self {
+        Self { assets }
+    }
+
+    /// Path the asset was loaded from, which is what scenes store instead of the asset itself.
+    /// Fails for assets that were created in code.
+    pub fn asset_path<T>(&self, asset: &Asset<T>) -> anyhow::Result<PathBuf> {
+        self.assets
+            .asset_path(asset.id())
+            .map(Path::to_path_buf)
+            .ok_or(anyhow::anyhow!(
+                "Asset {:?} was not loaded from a file, so it can't be saved in a scene.",
+                asset.id()
+            ))
+    }
+}
+
+/// Passed to `SceneComponent::restore`.
+pub struct SceneLoadContext<'a> {
+    assets: &'a mut AssetManager,
+    window: &'a Window,
+}
+
+impl<'a> SceneLoadContext<'a> {
+    pub(crate) fn new(assets: &'a mut AssetManager, window: &'a Window) -> Self {
+        Self { assets, window }
+    }
+
+    /// Loads an asset saved with `SceneSaveContext::asset_path`.
+    pub fn load_asset<T: Send + Sync + 'static>(&mut self, path: &Path) -> anyhow::Result<Asset<T>> {
+        Ok(self.assets.load(path)?)
+    }
+
+    pub fn assets(&mut self) -> &mut AssetManager {
+        self.assets
+    }
+
+    pub fn window(&self) -> &Window {
+        self.window
+    }
+}
+
+/// A component that is saved as some other data, for components that hold assets or GPU resources.
+/// Components that are plain data can be registered with `ComponentRegistry::register` instead.
+pub trait SceneComponent: hecs::Component + Sized {
+    type Data: Serialize + DeserializeOwned + 'static;
+
+    fn save(&self, context: &SceneSaveContext) -> anyhow::Result<Self::Data>;
+
+    fn restore(data: Self::Data, context: &mut SceneLoadContext) -> anyhow::Result<Self>;
+}
+
+/// Type erased access to one registered component type.
+trait ErasedComponent {
+    fn has(&self, entity: EntityRef<'_>) -> bool;
+
+    /// None if the entity doesn't have the component.
+    fn save<'w>(
+        &self,
+        entity: EntityRef<'w>,
+        context: &SceneSaveContext,
+    ) -> Option<anyhow::Result<Box<dyn erased_serde::Serialize + 'w>>>;
+
+    fn restore<'de>(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'de>,
+        entity: &mut EntityBuilder,
+        context: &mut SceneLoadContext,
+    ) -> anyhow::Result<()>;
+}
+
+/// Serializes a borrowed component without cloning it.
+struct Borrowed<'w, T>(hecs::Ref<'w, T>);
+
+impl<T: Serialize> Serialize for Borrowed<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self.0).serialize(serializer)
+    }
+}
+
+struct PlainComponent<T>(PhantomData<fn() -> T>);
+
+impl<T: hecs::Component + Serialize + DeserializeOwned> ErasedComponent for PlainComponent<T> {
+    fn has(&self, entity: EntityRef<'_>) -> bool {
+        entity.has::<T>()
+    }
+
+    fn save<'w>(
+        &self,
+        entity: EntityRef<'w>,
+        _context: &SceneSaveContext,
+    ) -> Option<anyhow::Result<Box<dyn erased_serde::Serialize + 'w>>> {
+        let component = entity.get::<&T>()?;
+
+        Some(Ok(Box::new(Borrowed(component))))
+    }
+
+    fn restore<'de>(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'de>,
+        entity: &mut EntityBuilder,
+        _context: &mut SceneLoadContext,
+    ) -> anyhow::Result<()> {
+        let component: T = erased_serde::deserialize(deserializer)?;
+        entity.add(component);
+
+        Ok(())
+    }
+}
+
+struct CustomComponent<T>(PhantomData<fn() -> T>);
+
+impl<T: SceneComponent> ErasedComponent for CustomComponent<T> {
+    fn has(&self, entity: EntityRef<'_>) -> bool {
+        entity.has::<T>()
+    }
+
+    fn save<'w>(
+        &self,
+        entity: EntityRef<'w>,
+        context: &SceneSaveContext,
+    ) -> Option<anyhow::Result<Box<dyn erased_serde::Serialize + 'w>>> {
+        let component = entity.get::<&T>()?;
+
+        Some(
+            component
+                .save(context)
+                .map(|data| Box::new(data) as Box<dyn erased_serde::Serialize + 'w>),
+        )
+    }
+
+    fn restore<'de>(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'de>,
+        entity: &mut EntityBuilder,
+        context: &mut SceneLoadContext,
+    ) -> anyhow::Result<()> {
+        let data: T::Data = erased_serde::deserialize(deserializer)?;
+        entity.add(T::restore(data, context)?);
+
+        Ok(())
+    }
+}
+
+struct RegisteredComponent {
+    name: String,
+    type_id: TypeId,
+    component: Box<dyn ErasedComponent>,
+}
+
+/// The components that are saved with scenes, each under a unique name.
+/// Components that aren't registered are left out when saving.
+/// The names end up in the files, so renaming one breaks scenes saved before.
+pub struct ComponentRegistry {
+    components: Vec<RegisteredComponent>,
+    by_name: AHashMap<String, usize>,
+    by_type: AHashMap<TypeId, usize>,
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComponentRegistry {
+    /// Creates a registry with the engine components already registered.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+
+        registry.register::<Transform>("Transform");
+        registry.register::<Parent>("Parent");
+        registry.register::<Children>("Children");
+
+        #[cfg(feature = "physics_2d")]
+        registry.register::<crate::physics_2d::rigidbody::Rigidbody2D>("Rigidbody2D");
+
+        #[cfg(feature = "renderer_2d")]
+        {
+            use crate::renderer_2d::renderables::{Rect, Sprite, Text};
+
+            registry.register::<Rect>("Rect");
+            registry.register_custom::<Sprite>("Sprite");
+            registry.register_custom::<Text>("Text");
+        }
+
+        registry
+    }
+
+    /// Creates a registry without any components.
+    pub fn empty() -> Self {
+        Self {
+            components: Vec::new(),
+            by_name: AHashMap::new(),
+            by_type: AHashMap::new(),
+        }
+    }
+
+    /// Registers a component that is saved as it is.
+    pub fn register<T: hecs::Component + Serialize + DeserializeOwned>(&mut self, name: &str) {
+        self.insert::<T>(name, Box::new(PlainComponent::<T>(PhantomData)));
+    }
+
+    /// Registers a component that is saved through `SceneComponent`.
+    pub fn register_custom<T: SceneComponent>(&mut self, name: &str) {
+        self.insert::<T>(name, Box::new(CustomComponent::<T>(PhantomData)));
+    }
+
+    pub fn is_registered<T: 'static>(&self) -> bool {
+        self.is_registered_id(TypeId::of::<T>())
+    }
+
+    pub(crate) fn is_registered_id(&self, type_id: TypeId) -> bool {
+        self.by_type.contains_key(&type_id)
+    }
+
+    /// Replaces the registration of the type, or of the name, if there already is one.
+    fn insert<T: 'static>(&mut self, name: &str, component: Box<dyn ErasedComponent>) {
+        let registered = RegisteredComponent {
+            name: name.to_string(),
+            type_id: TypeId::of::<T>(),
+            component,
+        };
+
+        let index = self
+            .by_type
+            .get(&TypeId::of::<T>())
+            .or(self.by_name.get(name))
+            .copied();
+
+        match index {
+            Some(index) => {
+                self.by_name.retain(|_, i| *i != index);
+                self.by_type.retain(|_, i| *i != index);
+                self.components[index] = registered;
+            }
+            None => self.components.push(registered),
+        }
+
+        let index = index.unwrap_or(self.components.len() - 1);
+        self.by_name.insert(name.to_string(), index);
+        self.by_type.insert(TypeId::of::<T>(), index);
+    }
+
+    fn get(&self, name: &str) -> Option<&RegisteredComponent> {
+        self.by_name.get(name).map(|&index| &self.components[index])
+    }
+
+    /// Registered components, leaving out the excluded types.
+    fn saved_components<'a>(
+        &'a self,
+        exclude: &'a [TypeId],
+    ) -> impl Iterator<Item = &'a RegisteredComponent> + 'a {
+        self.components
+            .iter()
+            .filter(move |registered| !exclude.contains(&registered.type_id))
+    }
+
+    /// Number of registered components the entity has.
+    fn component_count(&self, entity: EntityRef<'_>, exclude: &[TypeId]) -> usize {
+        self.saved_components(exclude)
+            .filter(|registered| registered.component.has(entity))
+            .count()
+    }
+
+    /// Writes every registered component of the entity into the map.
+    fn serialize_entity<S: SerializeMap>(
+        &self,
+        entity: EntityRef<'_>,
+        context: &SceneSaveContext,
+        exclude: &[TypeId],
+        map: &mut S,
+    ) -> Result<(), S::Error> {
+        for registered in self.saved_components(exclude) {
+            if let Some(value) = registered.component.save(entity, context) {
+                let value = value.map_err(|e| {
+                    <S::Error as ser::Error>::custom(format!(
+                        "Failed to save {}: {:#}",
+                        registered.name, e
+                    ))
+                })?;
+
+                map.serialize_entry(&registered.name, &*value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads components written by `serialize_entity` into the builder.
+    fn deserialize_entity<'de, M: MapAccess<'de>>(
+        &self,
+        map: &mut M,
+        entity: &mut EntityBuilder,
+        context: &mut SceneLoadContext,
+    ) -> Result<(), M::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            let registered = self.get(&name).ok_or_else(|| {
+                de::Error::custom(format!(
+                    "Unknown component {}, it has to be registered to be loaded.",
+                    name
+                ))
+            })?;
+
+            map.next_value_seed(ComponentSeed {
+                registered,
+                entity: &mut *entity,
+                context: &mut *context,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+struct WorldSaver<'a> {
+    registry: &'a ComponentRegistry,
+    context: &'a SceneSaveContext<'a>,
+}
+
+impl SerializeContext for WorldSaver<'_> {
+    fn serialize_entity<S: SerializeMap>(
+        &mut self,
+        entity: EntityRef<'_>,
+        mut map: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.registry.serialize_entity(entity, self.context, &[], &mut map)?;
+
+        map.end()
+    }
+
+    // Formats like bincode need to know the length up front
+    fn component_count(&self, entity: EntityRef<'_>) -> Option<usize> {
+        Some(self.registry.component_count(entity, &[]))
+    }
+}
+
+struct WorldLoader<'a, 'b> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+}
+
+impl DeserializeContext for WorldLoader<'_, '_> {
+    fn deserialize_entity<'de, M: MapAccess<'de>>(
+        &mut self,
+        mut map: M,
+        entity: &mut EntityBuilder,
+    ) -> Result<(), M::Error> {
+        self.registry.deserialize_entity(&mut map, entity, self.context)
+    }
+}
+
+/// The registered components of one entity, as a map from component names to their data.
+pub(crate) struct ComponentsRef<'a, 'w> {
+    pub registry: &'a ComponentRegistry,
+    pub entity: EntityRef<'w>,
+    pub context: &'a SceneSaveContext<'a>,
+    /// Registered components that are left out
+    pub exclude: &'a [TypeId],
+}
+
+impl Serialize for ComponentsRef<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let count = self.registry.component_count(self.entity, self.exclude);
+
+        let mut map = serializer.serialize_map(Some(count))?;
+        self.registry
+            .serialize_entity(self.entity, self.context, self.exclude, &mut map)?;
+
+        map.end()
+    }
+}
+
+/// Reads what `ComponentsRef` wrote into an entity builder.
+pub(crate) struct ComponentsSeed<'a, 'b> {
+    pub registry: &'a ComponentRegistry,
+    pub entity: &'a mut EntityBuilder,
+    pub context: &'a mut SceneLoadContext<'b>,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        self.registry
+            .deserialize_entity(&mut map, self.entity, self.context)
+    }
+}
+
+struct ComponentSeed<'a, 'b> {
+    registered: &'a RegisteredComponent,
+    entity: &'a mut EntityBuilder,
+    context: &'a mut SceneLoadContext<'b>,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentSeed<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+
+        self.registered
+            .component
+            .restore(&mut deserializer, self.entity, self.context)
+            .map_err(|e| {
+                <D::Error as de::Error>::custom(format!(
+                    "Failed to load {}: {:#}",
+                    self.registered.name, e
+                ))
+            })
+    }
+}
+
+struct WorldRef<'a> {
+    world: &'a hecs::World,
+    registry: &'a ComponentRegistry,
+    context: &'a SceneSaveContext<'a>,
+}
+
+impl Serialize for WorldRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut saver = WorldSaver {
+            registry: self.registry,
+            context: self.context,
+        };
+
+        hecs::serialize::row::serialize(self.world, &mut saver, serializer)
+    }
+}
+
+struct WorldSeed<'a, 'b> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+}
+
+impl<'de> DeserializeSeed<'de> for WorldSeed<'_, '_> {
+    type Value = hecs::World;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<hecs::World, D::Error> {
+        let mut loader = WorldLoader {
+            registry: self.registry,
+            context: self.context,
+        };
+
+        hecs::serialize::row::deserialize(&mut loader, deserializer)
+    }
+}
+
+/// The camera is saved without its GPU resources, which are recreated on load.
+#[derive(Serialize)]
+struct CameraRef<'a> {
+    transform: &'a Transform,
+    projection: &'a Projection,
+}
+
+#[derive(Deserialize)]
+struct CameraData {
+    transform: Transform,
+    projection: Projection,
+}
+
+struct SceneRef<'a> {
+    scene: &'a Scene,
+    registry: &'a ComponentRegistry,
+    context: &'a SceneSaveContext<'a>,
+}
+
+impl Serialize for SceneRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let camera = self.scene.camera.as_ref().map(|camera| CameraRef {
+            transform: &camera.transform,
+            projection: &camera.projection,
+        });
+
+        let mut state = serializer.serialize_struct("Scene", SCENE_FIELDS.len())?;
+        state.serialize_field("version", &SCENE_VERSION)?;
+        state.serialize_field("camera", &camera)?;
+        state.serialize_field(
+            "world",
+            &WorldRef {
+                world: &self.scene.world,
+                registry: self.registry,
+                context: self.context,
+            },
+        )?;
+        state.end()
+    }
+}
+
+struct SceneSeed<'a, 'b> {
+    registry: &'a ComponentRegistry,
+    context: &'a mut SceneLoadContext<'b>,
+}
+
+impl SceneSeed<'_, '_> {
+    fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+        if version != SCENE_VERSION {
+            return Err(E::custom(format!(
+                "Scene has version {}, only version {} is supported.",
+                version, SCENE_VERSION
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn build(&self, camera: Option<CameraData>, world: hecs::World) -> Scene {
+        Scene {
+            world,
+            camera: camera.map(|camera| {
+                Camera::new(camera.transform, camera.projection, self.context.window)
+            }),
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for SceneSeed<'_, '_> {
+    type Value = Scene;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Scene, D::Error> {
+        deserializer.deserialize_struct("Scene", SCENE_FIELDS, self)
+    }
+}
+
+impl<'de> Visitor<'de> for SceneSeed<'_, '_> {
+    type Value = Scene;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a scene")
+    }
+
+    // Binary formats store the fields in order
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Scene, A::Error> {
+        let version: u32 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &"a scene"))?;
+        Self::check_version::<A::Error>(version)?;
+
+        let camera: Option<CameraData> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &"a scene"))?;
+
+        let world = seq
+            .next_element_seed(WorldSeed {
+                registry: self.registry,
+                context: &mut *self.context,
+            })?
+            .ok_or_else(|| de::Error::invalid_length(2, &"a scene"))?;
+
+        Ok(self.build(camera, world))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Scene, A::Error> {
+        let mut version = None;
+        let mut camera = None;
+        let mut world = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => version = Some(map.next_value::<u32>()?),
+                "camera" => camera = Some(map.next_value::<Option<CameraData>>()?),
+                "world" => {
+                    // The version comes first, so the world is never read with the wrong layout
+                    let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+                    Self::check_version::<A::Error>(version)?;
+
+                    world = Some(map.next_value_seed(WorldSeed {
+                        registry: self.registry,
+                        context: &mut *self.context,
+                    })?);
+                }
+                _ => return Err(de::Error::unknown_field(&key, SCENE_FIELDS)),
+            }
+        }
+
+        let version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+        Self::check_version::<A::Error>(version)?;
+
+        let world = world.ok_or_else(|| de::Error::missing_field("world"))?;
+
+        Ok(self.build(camera.flatten(), world))
+    }
+}
+
+impl Scene {
+    /// Encodes the world and the camera.
+    /// Assets are stored as the paths they were loaded from.
+    pub fn to_bytes(
+        &self,
+        format: SceneFormat,
+        registry: &ComponentRegistry,
+        assets: &AssetManager,
+    ) -> anyhow::Result<Vec<u8>> {
+        let context = SceneSaveContext::new(assets);
+        let scene = SceneRef {
+            scene: self,
+            registry,
+            context: &context,
+        };
+
+        let bytes = match format {
+            SceneFormat::Ron => {
+                ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default())?.into_bytes()
+            }
+            SceneFormat::Binary => bincode::DefaultOptions::new().serialize(&scene)?,
+        };
+
+        Ok(bytes)
+    }
+
+    /// Decodes a scene written by `Scene::to_bytes`, loading the assets it uses.
+    /// Entities keep the ids they had when they were saved.
+    pub fn from_bytes(
+        bytes: &[u8],
+        format: SceneFormat,
+        registry: &ComponentRegistry,
+        assets: &mut AssetManager,
+        window: &Window,
+    ) -> anyhow::Result<Scene> {
+        let mut context = SceneLoadContext::new(assets, window);
+        let seed = SceneSeed {
+            registry,
+            context: &mut context,
+        };
+
+        let scene = match format {
+            SceneFormat::Ron => {
+                let mut deserializer = ron::Deserializer::from_bytes(bytes)?;
+                let scene = seed.deserialize(&mut deserializer)?;
+                deserializer.end()?;
+                scene
+            }
+            SceneFormat::Binary => bincode::DefaultOptions::new().deserialize_seed(seed, bytes)?,
+        };
+
+        Ok(scene)
+    }
+
+    /// Saves the scene to a file relative to the asset root.
+    /// The format is picked from the extension, see `SceneFormat::from_path`.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        registry: &ComponentRegistry,
+        assets: &AssetManager,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let bytes = self.to_bytes(SceneFormat::from_path(path), registry, assets)?;
+
+        std::fs::write(assets.root().join(path), bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to save scene '{}': {}", path.display(), e))
+    }
+
+    /// Loads a scene saved with `Scene::save`.
+    /// Like other assets, it is read from the mounted archive if it is in there.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        registry: &ComponentRegistry,
+        assets: &mut AssetManager,
+        window: &Window,
+    ) -> anyhow::Result<Scene> {
+        let path = path.as_ref();
+        let bytes = assets.read_file(path)?;
+
+        Self::from_bytes(&bytes, SceneFormat::from_path(path), registry, assets, window)
+            .map_err(|e| anyhow::anyhow!("Failed to load scene '{}': {:#}", path.display(), e))
+    }
+}
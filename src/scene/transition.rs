@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use crate::renderer::ScreenOverlay;
+
+use super::SceneChange;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// Fades to the color and back
+    Fade,
+    /// Covers the screen from left to right, then uncovers it
+    Wipe,
+}
+
+/// How the switch between two scenes is hidden.
+/// The scene is switched halfway through, when the screen is fully covered.
+#[derive(Debug, Clone, Copy)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+    /// RGBA color that covers the screen
+    pub color: [f32; 4],
+}
+
+impl Transition {
+    pub fn fade(duration: Duration) -> Self {
+        Self {
+            kind: TransitionKind::Fade,
+            duration,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn wipe(duration: Duration) -> Self {
+        Self {
+            kind: TransitionKind::Wipe,
+            duration,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+pub(crate) struct ActiveTransition {
+    /// Taken once the change has been queued
+    pub(crate) change: Option<SceneChange>,
+    transition: Transition,
+    started: Instant,
+}
+
+impl ActiveTransition {
+    pub(crate) fn new(change: SceneChange, transition: Transition) -> Self {
+        Self {
+            change: Some(change),
+            transition,
+            started: Instant::now(),
+        }
+    }
+
+    /// From 0 to 1 over the duration of the transition.
+    pub(crate) fn progress(&self) -> f32 {
+        if self.transition.duration.is_zero() {
+            return 1.0;
+        }
+
+        (self.started.elapsed().as_secs_f32() / self.transition.duration.as_secs_f32()).min(1.0)
+    }
+
+    /// How much of the screen is covered, 1 at the halfway point.
+    fn coverage(&self) -> f32 {
+        1.0 - (2.0 * self.progress() - 1.0).abs()
+    }
+
+    pub(crate) fn overlay(&self) -> ScreenOverlay {
+        let coverage = self.coverage();
+        let mut color = self.transition.color;
+
+        match self.transition.kind {
+            TransitionKind::Fade => {
+                color[3] *= coverage;
+
+                ScreenOverlay {
+                    color,
+                    rect: [0.0, 0.0, 1.0, 1.0],
+                }
+            }
+            TransitionKind::Wipe => {
+                // Grows from the left edge, then leaves through the right edge
+                let rect = if self.progress() < 0.5 {
+                    [0.0, 0.0, coverage, 1.0]
+                } else {
+                    [1.0 - coverage, 0.0, 1.0, 1.0]
+                };
+
+                ScreenOverlay { color, rect }
+            }
+        }
+    }
+}
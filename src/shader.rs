@@ -0,0 +1,63 @@
+use pollster::FutureExt;
+
+use crate::{
+    assets::{AssetError, AssetLoader, LoadContext},
+    window::Window,
+};
+
+/// A WGSL shader, loaded from a `.wgsl` file or created from source.
+/// Give it to `Renderer::set_shader` to draw a renderable type with it. It has to have the
+/// same entry points and bindings as the built-in shader it replaces.
+/// With hot reload enabled, the renderer rebuilds the pipeline every time the file changes.
+pub struct Shader {
+    source: String,
+    module: wgpu::ShaderModule,
+}
+
+impl Shader {
+    /// Compiles WGSL source, failing if it doesn't validate.
+    pub fn from_wgsl<S: Into<String>>(window: &Window, source: S) -> anyhow::Result<Self> {
+        Self::compile(&window.device, source.into())
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub(crate) fn module(&self) -> &wgpu::ShaderModule {
+        &self.module
+    }
+
+    fn compile(device: &wgpu::Device, source: String) -> anyhow::Result<Self> {
+        // Invalid shaders would otherwise panic in wgpu's error handler
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shader Asset"),
+            source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+        });
+
+        if let Some(e) = device.pop_error_scope().block_on() {
+            return Err(anyhow::anyhow!("Invalid shader: {}", e));
+        }
+
+        Ok(Self { source, module })
+    }
+}
+
+/// Loads `Shader` assets from WGSL files.
+pub struct ShaderLoader;
+
+impl AssetLoader for ShaderLoader {
+    type Asset = Shader;
+    type Decoded = String;
+
+    fn decode(&self, bytes: &[u8], context: &LoadContext) -> Result<String, AssetError> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| context.decode_error(e))
+    }
+
+    /// Compiling needs the GPU, so it happens here instead of in `decode`.
+    fn create(&self, source: String, context: &LoadContext) -> Result<Shader, AssetError> {
+        Shader::compile(context.device(), source).map_err(|e| context.decode_error(e))
+    }
+}
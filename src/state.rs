@@ -0,0 +1,217 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc, time::Instant};
+
+use crate::{
+    commands,
+    system::{Stage, System},
+    App,
+};
+
+/// A type that can be used as an app state, usually a fieldless enum.
+pub trait StateType: Clone + PartialEq + Debug + 'static {}
+
+impl<T: Clone + PartialEq + Debug + 'static> StateType for T {}
+
+/// How many states are kept for `State::back`.
+const HISTORY_LIMIT: usize = 32;
+
+/// Transitions that can follow each other in one frame, so hooks that keep requesting changes can't hang the app.
+const MAX_CHAINED_TRANSITIONS: usize = 8;
+
+enum StateRequest<S> {
+    Set(S),
+    Back,
+}
+
+/// The current state of type `S`, a resource added by `AppBuilder::add_state`.
+/// Changes are requested by systems and applied at the start of the next `Stage::PreUpdate`.
+pub struct State<S: StateType> {
+    current: S,
+    request: Option<StateRequest<S>>,
+    history: Vec<S>,
+    /// False until the on enter systems of the initial state have run
+    entered: bool,
+}
+
+impl<S: StateType> State<S> {
+    fn new(initial: S) -> Self {
+        Self {
+            current: initial,
+            request: None,
+            history: Vec::new(),
+            entered: false,
+        }
+    }
+
+    pub fn get(&self) -> &S {
+        &self.current
+    }
+
+    /// Requests a change to the state, the current state is remembered for `back`.
+    /// Replaces any change that was requested this frame.
+    pub fn set(&mut self, state: S) {
+        self.request = Some(StateRequest::Set(state));
+    }
+
+    /// Requests a change back to the previous state, if there is one.
+    pub fn back(&mut self) {
+        self.request = Some(StateRequest::Back);
+    }
+
+    /// The previous states, the most recent one last.
+    pub fn history(&self) -> &[S] {
+        &self.history
+    }
+
+    /// True if a change was requested and hasn't been applied yet.
+    pub fn is_changing(&self) -> bool {
+        self.request.is_some()
+    }
+
+    /// Applies the requested change, returning the old and new state.
+    fn apply_request(&mut self) -> Option<(S, S)> {
+        let next = match self.request.take()? {
+            StateRequest::Set(state) => {
+                if state == self.current {
+                    return None;
+                }
+
+                self.history.push(self.current.clone());
+                if self.history.len() > HISTORY_LIMIT {
+                    self.history.remove(0);
+                }
+
+                state
+            }
+            StateRequest::Back => self.history.pop()?,
+        };
+
+        let previous = std::mem::replace(&mut self.current, next.clone());
+
+        Some((previous, next))
+    }
+}
+
+/// Sent to `App::events` every time the state of type `S` changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateChanged<S: StateType> {
+    pub from: S,
+    pub to: S,
+}
+
+/// The systems that run when a state of type `S` is entered or left.
+pub(crate) struct StateSystems<S: StateType> {
+    on_enter: Vec<(S, System)>,
+    on_exit: Vec<(S, System)>,
+    /// False until the systems have been added to `App::systems`, so they can be disabled like any other
+    tracked: bool,
+}
+
+impl<S: StateType> StateSystems<S> {
+    pub(crate) fn new() -> Self {
+        Self {
+            on_enter: Vec::new(),
+            on_exit: Vec::new(),
+            tracked: false,
+        }
+    }
+
+    pub(crate) fn add_on_enter(&mut self, state: S, system: System) {
+        self.on_enter.push((state, system));
+    }
+
+    pub(crate) fn add_on_exit(&mut self, state: S, system: System) {
+        self.on_exit.push((state, system));
+    }
+}
+
+/// Creates the state resource.
+pub(crate) fn startup_system<S: StateType>(initial: S) -> System {
+    System::startup(
+        format!("Create state {}", std::any::type_name::<S>()),
+        move |app, _| {
+            if let Err(e) = app.resources.create_resource(State::new(initial.clone())) {
+                log::error!("Failed to create state: {}", e);
+            }
+        },
+    )
+}
+
+/// Applies requested changes to the state and runs the on exit and on enter systems.
+pub(crate) fn transition_system<S: StateType>(systems: Rc<RefCell<StateSystems<S>>>) -> System {
+    System::update(
+        format!("State transitions {}", std::any::type_name::<S>()),
+        None::<String>,
+        move |app, _| apply_transitions(app, &mut systems.borrow_mut()),
+    )
+    .in_stage(Stage::PreUpdate)
+}
+
+fn apply_transitions<S: StateType>(app: &mut App, systems: &mut StateSystems<S>) {
+    if !systems.tracked {
+        systems.tracked = true;
+
+        for (_, system) in systems.on_enter.iter().chain(systems.on_exit.iter()) {
+            app.systems.track(system);
+        }
+    }
+
+    let Some(state) = app.resources.get_resource_mut::<State<S>>() else {
+        return;
+    };
+
+    if !state.entered {
+        state.entered = true;
+
+        let initial = state.current.clone();
+        run_systems(app, &mut systems.on_enter, &initial);
+    }
+
+    for _ in 0..MAX_CHAINED_TRANSITIONS {
+        let Some((from, to)) = app
+            .resources
+            .get_resource_mut::<State<S>>()
+            .and_then(|state| state.apply_request())
+        else {
+            return;
+        };
+
+        log::info!("State changed from {:?} to {:?}.", from, to);
+
+        run_systems(app, &mut systems.on_exit, &from);
+        app.events.send(StateChanged {
+            from,
+            to: to.clone(),
+        });
+        run_systems(app, &mut systems.on_enter, &to);
+    }
+
+    let still_changing = app
+        .resources
+        .get_resource::<State<S>>()
+        .map(|state| state.is_changing())
+        .unwrap_or(false);
+
+    if still_changing {
+        log::warn!(
+            "State {} changed more than {} times in one frame, the rest is applied next frame.",
+            std::any::type_name::<S>(),
+            MAX_CHAINED_TRANSITIONS
+        );
+    }
+}
+
+/// Runs the systems bound to the state, they still have to pass their scene filter and run conditions.
+fn run_systems<S: StateType>(app: &mut App, systems: &mut [(S, System)], state: &S) {
+    for (system_state, system) in systems {
+        if system_state != state {
+            continue;
+        }
+
+        if system.should_run(app) {
+            (system.update)(app, &system.last_run.elapsed());
+            commands::apply(app);
+        }
+
+        system.last_run = Instant::now();
+    }
+}
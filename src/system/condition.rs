@@ -0,0 +1,101 @@
+use crate::{
+    state::{State, StateType},
+    time::Time,
+    App,
+};
+
+/// Decides every frame whether a system runs.
+/// Conditions can be combined with `and`, `or` and `!`.
+pub struct RunCondition {
+    check: Box<dyn FnMut(&App) -> bool>,
+}
+
+impl RunCondition {
+    pub fn new(f: impl FnMut(&App) -> bool + 'static) -> Self {
+        Self { check: Box::new(f) }
+    }
+
+    /// True while the scene is the current scene or an additive scene.
+    pub fn in_scene<S: Into<String>>(name: S) -> Self {
+        let name = name.into();
+
+        Self::new(move |app| app.scenes.is_active(&name))
+    }
+
+    /// True while a resource of type `T` exists.
+    pub fn resource_exists<T: 'static>() -> Self {
+        Self::new(|app| app.resources.get_resource::<T>().is_some())
+    }
+
+    /// True while the resource of type `T` equals the value, e.g. a game state enum.
+    pub fn resource_equals<T: PartialEq + 'static>(value: T) -> Self {
+        Self::new(move |app| {
+            app.resources
+                .get_resource::<T>()
+                .map(|resource| *resource == value)
+                .unwrap_or(false)
+        })
+    }
+
+    /// True while the app state of type `S` equals the value, see `AppBuilder::add_state`.
+    pub fn in_state<S: StateType>(state: S) -> Self {
+        Self::new(move |app| {
+            app.resources
+                .get_resource::<State<S>>()
+                .map(|current| *current.get() == state)
+                .unwrap_or(false)
+        })
+    }
+
+    /// True while game time runs, see `Time::pause`.
+    pub fn not_paused() -> Self {
+        Self::new(|app| {
+            app.resources
+                .get_resource::<Time>()
+                .map(|time| !time.is_paused())
+                .unwrap_or(true)
+        })
+    }
+
+    /// True on the first frame, then once every `n` frames.
+    pub fn every_n_frames(n: u32) -> Self {
+        let n = n.max(1);
+        let mut frame = 0;
+
+        Self::new(move |_| {
+            let run = frame % n == 0;
+            frame = (frame + 1) % n;
+            run
+        })
+    }
+
+    /// Both conditions are always checked, so frame counters stay in step.
+    pub fn and(mut self, mut other: RunCondition) -> Self {
+        Self::new(move |app| {
+            let a = self.check(app);
+            let b = other.check(app);
+            a && b
+        })
+    }
+
+    /// Both conditions are always checked, so frame counters stay in step.
+    pub fn or(mut self, mut other: RunCondition) -> Self {
+        Self::new(move |app| {
+            let a = self.check(app);
+            let b = other.check(app);
+            a || b
+        })
+    }
+
+    pub(crate) fn check(&mut self, app: &App) -> bool {
+        (self.check)(app)
+    }
+}
+
+impl std::ops::Not for RunCondition {
+    type Output = RunCondition;
+
+    fn not(mut self) -> Self::Output {
+        RunCondition::new(move |app| !self.check(app))
+    }
+}
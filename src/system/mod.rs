@@ -0,0 +1,222 @@
+use std::time::{Duration, Instant};
+
+use crate::{events::EventReader, App};
+
+pub mod condition;
+pub use condition::RunCondition;
+pub mod parallel;
+pub use parallel::{Access, SystemContext};
+pub mod registry;
+pub use registry::{SystemHandle, SystemRegistry, SystemSelector};
+pub(crate) mod schedule;
+
+use self::parallel::ParallelJob;
+
+/// Engine events that `System::event_callback` systems run on.
+/// Events with details also send them to `App::events`, e.g. `events::FileDropped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventCallbackType {
+    WindowResize,
+    /// Runs however the app exits
+    ShutDown,
+    /// The window's close button was pressed, callbacks can keep it open with `App::veto_close`
+    CloseRequested,
+    FocusGained,
+    FocusLost,
+    Minimized,
+    Restored,
+    /// Sends `events::WindowMoved`
+    Moved,
+    /// Sends `events::ScaleFactorChanged`
+    ScaleFactorChanged,
+    /// Sends `events::FileDropped` for every file
+    FileDropped,
+    /// Sends `events::SceneLoaded`, callbacks run once after the stage the scenes were loaded in
+    SceneLoaded,
+    /// Sends `events::SceneUnloaded`, callbacks run once after the stage the scenes were unloaded in
+    SceneUnloaded,
+    /// Before anything else in a frame
+    FrameBegin,
+    /// After everything else in a frame
+    FrameEnd,
+}
+
+pub(crate) enum SystemType {
+    Update,
+    Startup,
+    Timed(Duration),
+    EventCallback(EventCallbackType),
+}
+
+/// When a system runs within a frame. Stages run in this order,
+/// and `before`/`after` constraints order systems within a stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Before gameplay, e.g. to turn input into intents
+    PreUpdate,
+    /// The default stage
+    Update,
+    /// After gameplay, e.g. to react to what it changed
+    PostUpdate,
+    /// After transforms are propagated, right before the frame is drawn
+    PreRender,
+    /// After the frame is presented
+    PostRender,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::PreUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::PreRender,
+        Stage::PostRender,
+    ];
+}
+
+pub struct System {
+    pub name: String,
+    /// Does nothing for parallel systems, which run `parallel` instead.
+    /// Gets the real time since the system last ran, game time is in the `time::Time` resource
+    pub update: Box<dyn FnMut(&mut App, &Duration)>,
+    pub(crate) uuid: uuid::Uuid,
+    pub(crate) system_type: SystemType,
+    /// The first time, the delta will be off
+    /// So before App.run(), we need to set this to the current time
+    pub(crate) last_run: Instant,
+
+    pub(crate) scene: Option<String>,
+    /// Only used by update and timed systems
+    pub(crate) stage: Stage,
+    /// Names of the systems this one has to run before
+    pub(crate) before: Vec<String>,
+    /// Names of the systems this one has to run after
+    pub(crate) after: Vec<String>,
+    pub(crate) conditions: Vec<RunCondition>,
+    pub(crate) parallel: Option<ParallelJob>,
+}
+
+impl System {
+    fn new<T, S>(name: S, run: T, system_type: SystemType, scene: Option<String>) -> System
+    where T: FnMut(&mut App, &Duration) + 'static, S: Into<String>
+    {
+        System {
+            name: name.into(),
+            update: Box::new(run),
+            system_type,
+            uuid: uuid::Uuid::new_v4(),
+            last_run: Instant::now(),
+            scene,
+            stage: Stage::Update,
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            parallel: None,
+        }
+    }
+
+    pub fn startup<T, S>(name: S, run: T) -> System 
+    where T: FnMut(&mut App, &Duration) + 'static, S: Into<String>
+    {
+        System::new(name, run, SystemType::Startup, None)
+    }
+
+    pub fn event_callback<T, S>(name: S, run: T, event_type: EventCallbackType) -> System 
+    where T: FnMut(&mut App, &Duration) + 'static, S: Into<String>
+    {
+        System::new(name, run, SystemType::EventCallback(event_type), None)
+    }
+
+    pub fn timed<T, S>(name: S, scene: Option<impl Into<String>>, run: T, duration: Duration) -> System 
+    where T: FnMut(&mut App, &Duration) + 'static, S: Into<String>
+    {
+        System::new(name, run, SystemType::Timed(duration), scene.map(|s| s.into()))
+    }
+
+    pub fn update<T, S>(name: S, scene: Option<impl Into<String>>, run: T) -> System 
+    where T: FnMut(&mut App, &Duration) + 'static, S: Into<String>
+    {
+        System::new(name, run, SystemType::Update, scene.map(|s| s.into()))
+    }
+
+    /// A system that runs once for every event of type `E` sent since it last ran, see `Events`.
+    /// It runs in its stage like an update system, so it can be ordered and given run conditions.
+    pub fn on_event<E, T, S>(name: S, scene: Option<impl Into<String>>, mut run: T) -> System
+    where E: Clone + 'static, T: FnMut(&mut App, &E) + 'static, S: Into<String>
+    {
+        let mut reader = EventReader::<E>::new();
+
+        let update = move |app: &mut App, _: &Duration| {
+            // Copied out so the handler can send events and change the app
+            let events: Vec<E> = reader.read(&app.events).cloned().collect();
+
+            for event in &events {
+                run(app, event);
+            }
+        };
+
+        System::new(name, update, SystemType::Update, scene.map(|s| s.into()))
+    }
+
+    /// A system that runs every frame on the thread pool, at the same time as other parallel systems
+    /// in the same stage whose access doesn't conflict with it.
+    /// It sees its scene's world, or the current scene's world if it has no scene,
+    /// and only queries the components and resources in `access`, so it can't load scenes or queue commands.
+    pub fn parallel<T, S>(name: S, scene: Option<impl Into<String>>, access: Access, run: T) -> System
+    where T: FnMut(&mut SystemContext, &Duration) + Send + 'static, S: Into<String>
+    {
+        let mut system = System::new(name, |_: &mut App, _: &Duration| {}, SystemType::Update, scene.map(|s| s.into()));
+        system.parallel = Some(ParallelJob {
+            access,
+            run: Box::new(run),
+        });
+
+        system
+    }
+
+    /// Identifies the system in `App::systems` once it is registered.
+    pub fn handle(&self) -> SystemHandle {
+        SystemHandle(self.uuid)
+    }
+
+    /// Runs the system in a stage other than `Stage::Update`.
+    pub fn in_stage(mut self, stage: Stage) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    /// Runs the system before every system with this name in the same stage.
+    pub fn before<S: Into<String>>(mut self, name: S) -> Self {
+        self.before.push(name.into());
+        self
+    }
+
+    /// Runs the system after every system with this name in the same stage.
+    pub fn after<S: Into<String>>(mut self, name: S) -> Self {
+        self.after.push(name.into());
+        self
+    }
+
+    /// Only runs the system while the condition holds.
+    /// Adding more than one condition requires all of them to hold.
+    pub fn run_if(mut self, condition: RunCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Checks whether the system is enabled, its scene and the run conditions.
+    /// Every condition is checked, even after one fails, so frame counters stay in step.
+    pub(crate) fn should_run(&mut self, app: &App) -> bool {
+        let enabled = app.systems.is_enabled(self.handle());
+
+        let in_scene = self
+            .scene
+            .as_ref()
+            .map(|scene| app.scenes.is_active(scene))
+            .unwrap_or(true);
+
+        self.conditions
+            .iter_mut()
+            .fold(enabled && in_scene, |run, condition| condition.check(app) && run)
+    }
+}
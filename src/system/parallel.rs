@@ -0,0 +1,222 @@
+use std::{
+    any::{Any, TypeId},
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+
+use crate::{
+    resources::{self, ResourcePtr},
+    App,
+};
+
+use super::System;
+
+/// The components and resources a parallel system reads and writes.
+/// Systems whose access doesn't conflict may run at the same time.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+    resource_reads: Vec<TypeId>,
+    resource_writes: Vec<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The system queries `&T`.
+    pub fn read<T: hecs::Component>(mut self) -> Self {
+        self.reads.push(TypeId::of::<T>());
+        self
+    }
+
+    /// The system queries `&mut T`.
+    pub fn write<T: hecs::Component>(mut self) -> Self {
+        self.writes.push(TypeId::of::<T>());
+        self
+    }
+
+    /// The system reads the resource with `SystemContext::resource`.
+    pub fn read_resource<T: Send + Sync + 'static>(mut self) -> Self {
+        self.resource_reads.push(TypeId::of::<T>());
+        self
+    }
+
+    /// The system changes the resource with `SystemContext::resource_mut`.
+    pub fn write_resource<T: Send + Sync + 'static>(mut self) -> Self {
+        self.resource_writes.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Checks that a query only uses declared components, `&mut T` has to be declared with `write`.
+    fn check_query<Q: hecs::Query>(&self) -> anyhow::Result<()> {
+        let mut undeclared = false;
+
+        <Q::Fetch as hecs::Fetch>::for_each_borrow(|type_id, unique| {
+            let declared = if unique {
+                self.writes.contains(&type_id)
+            } else {
+                self.reads.contains(&type_id) || self.writes.contains(&type_id)
+            };
+
+            undeclared |= !declared;
+        });
+
+        if undeclared {
+            return Err(anyhow::anyhow!(
+                "Query {} uses components that weren't declared in the system's access.",
+                std::any::type_name::<Q>()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// True if one of the systems writes something the other reads or writes.
+    pub(crate) fn conflicts(&self, other: &Access) -> bool {
+        fn overlaps(a: &[TypeId], b: &[TypeId]) -> bool {
+            a.iter().any(|id| b.contains(id))
+        }
+
+        overlaps(&self.writes, &other.reads)
+            || overlaps(&self.writes, &other.writes)
+            || overlaps(&other.writes, &self.reads)
+            || overlaps(&self.resource_writes, &other.resource_reads)
+            || overlaps(&self.resource_writes, &other.resource_writes)
+            || overlaps(&other.resource_writes, &self.resource_reads)
+    }
+}
+
+pub(crate) struct ParallelJob {
+    pub(crate) access: Access,
+    pub(crate) run: Box<dyn FnMut(&mut SystemContext, &Duration) + Send>,
+}
+
+/// What a parallel system can reach: the components and resources it declared,
+/// in the world of its scene or of the current scene if it has none.
+pub struct SystemContext<'w> {
+    world: &'w hecs::World,
+    access: &'w Access,
+    resources: AHashMap<TypeId, ResourcePtr>,
+}
+
+// Safety: resources can only be declared if they are Send + Sync, the pointers of a batch are
+// taken once per resource, and the scheduler never runs a system that writes a resource
+// at the same time as any other system that reads or writes it
+unsafe impl Send for SystemContext<'_> {}
+
+impl<'w> SystemContext<'w> {
+    /// `ptrs` holds the resources of the whole batch, the context keeps the ones the system declared.
+    fn new(world: &'w hecs::World, access: &'w Access, ptrs: &AHashMap<TypeId, ResourcePtr>) -> Self {
+        let declared = access
+            .resource_reads
+            .iter()
+            .chain(&access.resource_writes)
+            .filter_map(|type_id| Some((*type_id, *ptrs.get(type_id)?)))
+            .collect();
+
+        Self {
+            world,
+            access,
+            resources: declared,
+        }
+    }
+
+    /// Queries the world, e.g. `context.query::<(&mut Transform, &Velocity)>()`.
+    /// Fails if the query uses a component the system didn't declare, `&mut T` needs `Access::write`.
+    pub fn query<Q: hecs::Query>(&self) -> anyhow::Result<hecs::QueryBorrow<'w, Q>> {
+        self.access.check_query::<Q>()?;
+
+        Ok(self.world.query::<Q>())
+    }
+
+    /// A resource the system declared it reads or writes.
+    pub fn resource<T: 'static>(&self) -> Option<&T> {
+        let res = match *self.resources.get(&TypeId::of::<T>())? {
+            ResourcePtr::Read(ptr) => ptr,
+            ResourcePtr::Write(ptr) => ptr as *const dyn Any,
+        };
+
+        // Safety: see `SystemContext`, the returned reference borrows self so it can't alias `resource_mut`
+        resources::downcast_resource::<T>(unsafe { &*res })
+    }
+
+    /// A resource the system declared it writes.
+    pub fn resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        let ResourcePtr::Write(res) = *self.resources.get(&TypeId::of::<T>())? else {
+            log::error!(
+                "Resource {} was declared as read only.",
+                std::any::type_name::<T>()
+            );
+            return None;
+        };
+
+        resources::downcast_resource_mut::<T>(unsafe { &mut *res })
+    }
+}
+
+/// True if the system can run at the same time as every system in the batch.
+pub(crate) fn can_join(batch: &[System], system: &System) -> bool {
+    let Some(job) = &system.parallel else {
+        return false;
+    };
+
+    batch.iter().all(|other| {
+        let Some(other_job) = &other.parallel else {
+            return false;
+        };
+
+        // Ordering constraints still hold between parallel systems
+        let ordered = system.before.contains(&other.name)
+            || system.after.contains(&other.name)
+            || other.before.contains(&system.name)
+            || other.after.contains(&system.name);
+
+        !ordered && !job.access.conflicts(&other_job.access)
+    })
+}
+
+/// Runs a batch of parallel systems on the thread pool and waits for all of them.
+pub(crate) fn run_batch(systems: &mut [System], app: &mut App) {
+    let should_run: Vec<bool> = systems.iter_mut().map(|system| system.should_run(app)).collect();
+
+    let scenes = &app.scenes;
+
+    // Every resource the batch uses is borrowed once, the resource manager itself can't be sent to other threads
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    for job in systems.iter().filter_map(|system| system.parallel.as_ref()) {
+        reads.extend_from_slice(&job.access.resource_reads);
+        writes.extend_from_slice(&job.access.resource_writes);
+    }
+    let ptrs = app.resources.resource_ptrs(&reads, &writes);
+
+    let mut jobs = Vec::new();
+    for (system, run_system) in systems.iter_mut().zip(should_run) {
+        let delta = system.last_run.elapsed();
+        system.last_run = Instant::now();
+
+        // Systems bound to a scene see its world, e.g. an additive HUD scene
+        let scene = match &system.scene {
+            Some(name) => scenes.scene(name),
+            None => scenes.current_scene(),
+        };
+
+        let (Some(scene), Some(ParallelJob { access, run })) = (scene, &mut system.parallel) else {
+            continue;
+        };
+
+        if run_system {
+            jobs.push((run, SystemContext::new(&scene.world, access, &ptrs), delta));
+        }
+    }
+
+    rayon::scope(|scope| {
+        for (run, mut context, delta) in jobs {
+            scope.spawn(move |_| run(&mut context, &delta));
+        }
+    });
+}
@@ -0,0 +1,136 @@
+use ahash::{AHashMap, AHashSet};
+
+use super::System;
+
+/// Identifies one registered system, returned by `System::handle` and `SystemRegistry::add`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemHandle(pub(crate) uuid::Uuid);
+
+/// A system by handle, or every system with a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemSelector {
+    Handle(SystemHandle),
+    Name(String),
+}
+
+impl From<SystemHandle> for SystemSelector {
+    fn from(handle: SystemHandle) -> Self {
+        SystemSelector::Handle(handle)
+    }
+}
+
+impl From<&str> for SystemSelector {
+    fn from(name: &str) -> Self {
+        SystemSelector::Name(name.to_string())
+    }
+}
+
+impl From<String> for SystemSelector {
+    fn from(name: String) -> Self {
+        SystemSelector::Name(name)
+    }
+}
+
+pub(crate) enum RegistryChange {
+    Add(System),
+    Remove(SystemHandle),
+}
+
+/// Adds, removes, enables and disables systems while the app is running.
+/// Enabling and disabling takes effect right away. Added and removed systems
+/// are picked up at the start of the next stage, startup systems added this way run once at that point.
+pub struct SystemRegistry {
+    names: AHashMap<SystemHandle, String>,
+    disabled: AHashSet<SystemHandle>,
+    changes: Vec<RegistryChange>,
+}
+
+impl SystemRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            names: AHashMap::new(),
+            disabled: AHashSet::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, system: System) -> SystemHandle {
+        let handle = system.handle();
+
+        self.track(&system);
+        self.changes.push(RegistryChange::Add(system));
+
+        handle
+    }
+
+    pub fn remove(&mut self, system: impl Into<SystemSelector>) {
+        for handle in self.resolve(&system.into()) {
+            self.untrack(handle);
+            self.changes.push(RegistryChange::Remove(handle));
+        }
+    }
+
+    /// Selecting by name only affects systems with that name that are registered right now.
+    pub fn enable(&mut self, system: impl Into<SystemSelector>) {
+        self.set_enabled(system, true);
+    }
+
+    /// A disabled system is skipped until it is enabled again, like a system whose run condition fails.
+    pub fn disable(&mut self, system: impl Into<SystemSelector>) {
+        self.set_enabled(system, false);
+    }
+
+    pub fn set_enabled(&mut self, system: impl Into<SystemSelector>, enabled: bool) {
+        for handle in self.resolve(&system.into()) {
+            if enabled {
+                self.disabled.remove(&handle);
+            } else {
+                self.disabled.insert(handle);
+            }
+        }
+    }
+
+    /// False for disabled systems, and for systems that aren't registered.
+    pub fn is_enabled(&self, handle: SystemHandle) -> bool {
+        self.names.contains_key(&handle) && !self.disabled.contains(&handle)
+    }
+
+    pub fn contains(&self, system: impl Into<SystemSelector>) -> bool {
+        !self.resolve(&system.into()).is_empty()
+    }
+
+    /// Handles of the registered systems with this name.
+    pub fn handles(&self, name: &str) -> Vec<SystemHandle> {
+        self.resolve(&SystemSelector::Name(name.to_string()))
+    }
+
+    fn resolve(&self, system: &SystemSelector) -> Vec<SystemHandle> {
+        match system {
+            SystemSelector::Handle(handle) => self
+                .names
+                .contains_key(handle)
+                .then_some(*handle)
+                .into_iter()
+                .collect(),
+            SystemSelector::Name(name) => self
+                .names
+                .iter()
+                .filter(|(_, system_name)| *system_name == name)
+                .map(|(handle, _)| *handle)
+                .collect(),
+        }
+    }
+
+    pub(crate) fn track(&mut self, system: &System) {
+        self.names.insert(system.handle(), system.name.clone());
+    }
+
+    pub(crate) fn untrack(&mut self, handle: SystemHandle) {
+        self.names.remove(&handle);
+        self.disabled.remove(&handle);
+    }
+
+    pub(crate) fn take_changes(&mut self) -> Vec<RegistryChange> {
+        std::mem::take(&mut self.changes)
+    }
+}
@@ -0,0 +1,445 @@
+use std::time::Instant;
+
+use ahash::AHashMap;
+
+use crate::{
+    commands,
+    events::{EventReader, SceneLoaded, SceneUnloaded},
+    App,
+};
+
+use super::{
+    parallel,
+    registry::{RegistryChange, SystemRegistry},
+    EventCallbackType, Stage, System, SystemType,
+};
+
+/// The registered systems sorted into the order they run in.
+pub(crate) struct Schedule {
+    startup: Vec<System>,
+    stages: Vec<(Stage, Vec<System>)>,
+    events: Vec<System>,
+    scenes_loaded: EventReader<SceneLoaded>,
+    scenes_unloaded: EventReader<SceneUnloaded>,
+}
+
+impl Schedule {
+    /// Sorts the systems by stage and ordering constraints.
+    /// Fails if a constraint names a system that doesn't exist, contradicts the stage order or is part of a cycle.
+    pub(crate) fn new(systems: Vec<System>) -> anyhow::Result<Self> {
+        check_stages(&systems.iter().collect::<Vec<_>>())?;
+
+        let mut startup = Vec::new();
+        let mut events = Vec::new();
+        let mut staged: AHashMap<Stage, Vec<System>> = AHashMap::new();
+
+        for system in systems {
+            match system.system_type {
+                SystemType::Startup => startup.push(system),
+                SystemType::EventCallback(_) => events.push(system),
+                SystemType::Update | SystemType::Timed(_) => {
+                    staged.entry(system.stage).or_default().push(system)
+                }
+            }
+        }
+
+        let mut stages = Vec::new();
+        for stage in Stage::ALL {
+            let systems = staged.remove(&stage).unwrap_or_default();
+            stages.push((stage, sort(systems)?));
+        }
+
+        Ok(Self {
+            startup: sort(startup)?,
+            stages,
+            events: sort(events)?,
+            scenes_loaded: EventReader::new(),
+            scenes_unloaded: EventReader::new(),
+        })
+    }
+
+    /// The first time, the delta will be off
+    /// So right before the first frame, set every timer to the current time
+    pub(crate) fn reset_timers(&mut self) {
+        let now = Instant::now();
+
+        for system in self.systems_mut() {
+            system.last_run = now;
+        }
+    }
+
+    /// Lets the registry find the systems the app was built with.
+    /// Startup systems are left out, they are gone after the first frame.
+    pub(crate) fn track(&self, registry: &mut SystemRegistry) {
+        for system in self.systems() {
+            registry.track(system);
+        }
+    }
+
+    /// Applies the systems added and removed through `App::systems`.
+    fn apply_registry(&mut self, app: &mut App) {
+        for change in app.systems.take_changes() {
+            match change {
+                RegistryChange::Add(mut system) => {
+                    if let SystemType::Startup = system.system_type {
+                        (system.update)(app, &system.last_run.elapsed());
+                        commands::apply(app);
+                        app.systems.untrack(system.handle());
+                        continue;
+                    }
+
+                    let handle = system.handle();
+                    if let Err(e) = self.insert(system) {
+                        log::error!("Failed to add system: {}", e);
+                        app.systems.untrack(handle);
+                    }
+                }
+                RegistryChange::Remove(handle) => {
+                    for (_, systems) in &mut self.stages {
+                        systems.retain(|system| system.handle() != handle);
+                    }
+                    self.events.retain(|system| system.handle() != handle);
+                }
+            }
+        }
+    }
+
+    /// Adds a system at runtime, keeping the ordering constraints.
+    fn insert(&mut self, mut system: System) -> anyhow::Result<()> {
+        let mut others: Vec<&System> = self.systems().collect();
+        others.push(&system);
+        check_stages(&others)?;
+
+        system.last_run = Instant::now();
+
+        let systems = match system.system_type {
+            SystemType::EventCallback(_) => &mut self.events,
+            _ => {
+                let stage = system.stage;
+                &mut self
+                    .stages
+                    .iter_mut()
+                    .find(|(s, _)| *s == stage)
+                    .ok_or(anyhow::anyhow!("Stage {:?} not found.", stage))?
+                    .1
+            }
+        };
+
+        systems.push(system);
+
+        match order(systems) {
+            Ok(order) => {
+                *systems = permute(std::mem::take(systems), order);
+                Ok(())
+            }
+            Err(e) => {
+                systems.pop();
+                Err(e)
+            }
+        }
+    }
+
+    fn systems(&self) -> impl Iterator<Item = &System> {
+        self.stages
+            .iter()
+            .flat_map(|(_, systems)| systems.iter())
+            .chain(self.events.iter())
+    }
+
+    fn systems_mut(&mut self) -> impl Iterator<Item = &mut System> {
+        self.startup
+            .iter_mut()
+            .chain(self.stages.iter_mut().flat_map(|(_, systems)| systems.iter_mut()))
+            .chain(self.events.iter_mut())
+    }
+
+    /// Runs the startup systems once, they are dropped afterwards.
+    pub(crate) fn run_startup(&mut self, app: &mut App) {
+        for mut system in std::mem::take(&mut self.startup) {
+            (system.update)(app, &system.last_run.elapsed());
+            commands::apply(app);
+        }
+
+        self.dispatch_scene_events(app);
+    }
+
+    /// Runs the scene loaded and unloaded callbacks once if any scene changed since the last call.
+    pub(crate) fn dispatch_scene_events(&mut self, app: &mut App) {
+        if self.scenes_unloaded.read(&app.events).count() > 0 {
+            self.run_event(EventCallbackType::SceneUnloaded, app);
+        }
+
+        if self.scenes_loaded.read(&app.events).count() > 0 {
+            self.run_event(EventCallbackType::SceneLoaded, app);
+        }
+    }
+
+    pub(crate) fn run_stage(&mut self, stage: Stage, app: &mut App) {
+        self.apply_registry(app);
+
+        let Some((_, systems)) = self.stages.iter_mut().find(|(s, _)| *s == stage) else {
+            return;
+        };
+
+        let mut i = 0;
+        while i < systems.len() {
+            // Consecutive parallel systems that don't conflict run together
+            if systems[i].parallel.is_some() {
+                let start = i;
+                i += 1;
+
+                while i < systems.len() && parallel::can_join(&systems[start..i], &systems[i]) {
+                    i += 1;
+                }
+
+                parallel::run_batch(&mut systems[start..i], app);
+                continue;
+            }
+
+            let system = &mut systems[i];
+            i += 1;
+
+            let run_system = system.should_run(app);
+
+            match system.system_type {
+                SystemType::Timed(duration) => {
+                    if system.last_run.elapsed() >= duration {
+                        // Still loop and check to track last_run times
+                        if run_system {
+                            (system.update)(app, &system.last_run.elapsed());
+                            commands::apply(app);
+                        }
+                        system.last_run = Instant::now();
+                    }
+                }
+                SystemType::Update => {
+                    // Still loop and check to track last_run times
+                    if run_system {
+                        (system.update)(app, &system.last_run.elapsed());
+                        commands::apply(app);
+                    }
+                    system.last_run = Instant::now();
+                }
+                _ => {}
+            }
+        }
+
+        self.dispatch_scene_events(app);
+    }
+
+    pub(crate) fn run_event(&mut self, event: EventCallbackType, app: &mut App) {
+        self.apply_registry(app);
+
+        for system in &mut self.events {
+            if let SystemType::EventCallback(e) = &system.system_type {
+                if *e == event {
+                    if system.should_run(app) {
+                        (system.update)(app, &system.last_run.elapsed());
+                        commands::apply(app);
+                    }
+
+                    // Update delta time
+                    system.last_run = Instant::now();
+                }
+            }
+        }
+    }
+}
+
+/// Which systems a constraint can order, systems in other groups are never compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    Startup,
+    Event,
+    Stage(Stage),
+}
+
+fn group(system: &System) -> Group {
+    match system.system_type {
+        SystemType::Startup => Group::Startup,
+        SystemType::EventCallback(_) => Group::Event,
+        SystemType::Update | SystemType::Timed(_) => Group::Stage(system.stage),
+    }
+}
+
+/// Checks that every constraint names a system, and that constraints between stages agree with the stage order.
+fn check_stages(systems: &[&System]) -> anyhow::Result<()> {
+    for &system in systems {
+        let constraints = system
+            .before
+            .iter()
+            .map(|name| (name, true))
+            .chain(system.after.iter().map(|name| (name, false)));
+
+        for (name, before) in constraints {
+            let others: Vec<&System> = systems
+                .iter()
+                .copied()
+                .filter(|other| other.name == *name)
+                .collect();
+
+            if others.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "System '{}' is ordered relative to '{}', which isn't registered.",
+                    system.name,
+                    name
+                ));
+            }
+
+            for other in others {
+                let (Group::Stage(stage), Group::Stage(other_stage)) = (group(system), group(other)) else {
+                    continue;
+                };
+
+                let contradicts = if before {
+                    stage > other_stage
+                } else {
+                    stage < other_stage
+                };
+
+                if contradicts {
+                    return Err(anyhow::anyhow!(
+                        "System '{}' has to run {} '{}', but it is in stage {:?} and '{}' is in stage {:?}.",
+                        system.name,
+                        if before { "before" } else { "after" },
+                        name,
+                        stage,
+                        name,
+                        other_stage
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Orders systems of the same group so every constraint between them holds.
+/// Systems that aren't constrained keep their registration order.
+fn sort(systems: Vec<System>) -> anyhow::Result<Vec<System>> {
+    let order = order(&systems)?;
+
+    Ok(permute(systems, order))
+}
+
+/// The indices of the systems in the order they have to run in.
+fn order(systems: &[System]) -> anyhow::Result<Vec<usize>> {
+    let mut by_name: AHashMap<&str, Vec<usize>> = AHashMap::new();
+    for (i, system) in systems.iter().enumerate() {
+        by_name.entry(system.name.as_str()).or_default().push(i);
+    }
+
+    // edges[a] holds the systems that have to run after a
+    let mut edges: Vec<Vec<usize>> = vec![Vec::new(); systems.len()];
+    for (i, system) in systems.iter().enumerate() {
+        for name in &system.before {
+            for &other in by_name.get(name.as_str()).into_iter().flatten() {
+                edges[i].push(other);
+            }
+        }
+
+        for name in &system.after {
+            for &other in by_name.get(name.as_str()).into_iter().flatten() {
+                edges[other].push(i);
+            }
+        }
+    }
+
+    let mut incoming = vec![0; systems.len()];
+    for targets in &edges {
+        for &target in targets {
+            incoming[target] += 1;
+        }
+    }
+
+    let mut order = Vec::with_capacity(systems.len());
+    let mut done = vec![false; systems.len()];
+    while order.len() < systems.len() {
+        // The earliest registered system that has nothing left to wait for
+        let Some(next) = (0..systems.len()).find(|&i| !done[i] && incoming[i] == 0) else {
+            let cycle: Vec<&str> = (0..systems.len())
+                .filter(|&i| !done[i])
+                .map(|i| systems[i].name.as_str())
+                .collect();
+
+            return Err(anyhow::anyhow!(
+                "Systems have cyclic ordering constraints: {}",
+                cycle.join(", ")
+            ));
+        };
+
+        done[next] = true;
+        order.push(next);
+
+        for &target in &edges[next] {
+            incoming[target] -= 1;
+        }
+    }
+
+    Ok(order)
+}
+
+fn permute(systems: Vec<System>, order: Vec<usize>) -> Vec<System> {
+    let mut systems: Vec<Option<System>> = systems.into_iter().map(Some).collect();
+
+    order
+        .into_iter()
+        .filter_map(|i| systems[i].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(name: &str) -> System {
+        System::update(name, None::<String>, |_, _| {})
+    }
+
+    fn names(systems: &[System], order: &[usize]) -> Vec<String> {
+        order.iter().map(|&i| systems[i].name.clone()).collect()
+    }
+
+    #[test]
+    fn unconstrained_systems_keep_registration_order() {
+        let systems = vec![system("a"), system("b"), system("c")];
+
+        let order = order(&systems).unwrap();
+        assert_eq!(names(&systems, &order), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn constraints_move_only_the_constrained_systems() {
+        let systems = vec![system("a"), system("b"), system("c").before("a"), system("d").after("a")];
+
+        let order = order(&systems).unwrap();
+        assert_eq!(names(&systems, &order), ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn cycle_is_an_error() {
+        let systems = vec![system("a").before("b"), system("b").before("c"), system("c").after("a").before("a")];
+
+        assert!(order(&systems).is_err());
+        assert!(Schedule::new(systems).is_err());
+    }
+
+    #[test]
+    fn unknown_system_is_an_error() {
+        let systems = [system("a").after("missing")];
+
+        assert!(check_stages(&systems.iter().collect::<Vec<_>>()).is_err());
+    }
+
+    #[test]
+    fn constraint_against_stage_order_is_an_error() {
+        let late = system("late").in_stage(Stage::PostUpdate).before("early");
+        let early = system("early").in_stage(Stage::Update);
+
+        assert!(check_stages(&[&late, &early]).is_err());
+
+        let late = system("late").in_stage(Stage::PostUpdate).after("early");
+        assert!(check_stages(&[&late, &early]).is_ok());
+    }
+}
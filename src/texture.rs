@@ -1,4 +1,72 @@
-use crate::window::Window;
+use crate::{
+    assets::{AssetError, AssetLoader, LoadContext},
+    window::Window,
+};
+
+/// How the texture is sampled between pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Sharp pixels, best for pixel art
+    Nearest,
+    /// Smooth interpolation, best for high resolution art
+    Linear,
+}
+
+impl From<TextureFilter> for wgpu::FilterMode {
+    fn from(filter: TextureFilter) -> Self {
+        match filter {
+            TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+            TextureFilter::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+/// How texture coordinates outside of 0.0 to 1.0 are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureWrap {
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+impl From<TextureWrap> for wgpu::AddressMode {
+    fn from(wrap: TextureWrap) -> Self {
+        match wrap {
+            TextureWrap::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            TextureWrap::Repeat => wgpu::AddressMode::Repeat,
+            TextureWrap::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// Import settings used when a texture is created.
+/// The default matches pixel art: nearest filtering, clamped edges, no mipmaps and sRGB colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureSettings {
+    pub filter: TextureFilter,
+    pub wrap: TextureWrap,
+    /// Generates a full mip chain on the CPU, which avoids shimmering when the texture is
+    /// drawn smaller than its size. Only useful with linear filtering.
+    pub generate_mipmaps: bool,
+    /// If true, the color data is treated as sRGB, which is the case for most images.
+    /// Disable it for data textures such as normal maps.
+    pub srgb: bool,
+    /// Multiplies the color channels by alpha on import.
+    /// This avoids dark fringes around transparent edges when using linear filtering.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        Self {
+            filter: TextureFilter::Nearest,
+            wrap: TextureWrap::ClampToEdge,
+            generate_mipmaps: false,
+            srgb: true,
+            premultiply_alpha: false,
+        }
+    }
+}
 
 pub struct Texture {
     pub(crate) texture: wgpu::Texture,
@@ -6,9 +74,11 @@ pub struct Texture {
     pub(crate) sampler: wgpu::Sampler,
     pub(crate) bind_group: wgpu::BindGroup,
     pub(crate) size: wgpu::Extent3d,
+    pub(crate) settings: TextureSettings,
+    /// Dynamic textures can be written to from the CPU after creation
+    dynamic: bool,
 }
 
-
 impl Texture {
     pub(crate) fn get_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
         device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -36,86 +106,454 @@ impl Texture {
         })
     }
 
-    pub fn save(&self, window: &Window) -> anyhow::Result<()> {
-        unimplemented!("Saving textures is not yet implemented")
+    fn create_bind_group(
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let bind_group_layout = Self::get_bind_group_layout(device);
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
     }
 
-    pub fn load(window: &crate::window::Window, bytes: &[u8]) -> Self {
-        let img = image::load_from_memory(bytes).expect("Failed to load texture");
+    fn create_sampler(device: &wgpu::Device, settings: &TextureSettings) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: settings.wrap.into(),
+            address_mode_v: settings.wrap.into(),
+            address_mode_w: settings.wrap.into(),
+            mag_filter: settings.filter.into(),
+            min_filter: settings.filter.into(),
+            mipmap_filter: settings.filter.into(),
+            ..Default::default()
+        })
+    }
 
-        let rgba = img.into_rgba8();
+    /// Creates an empty texture that can be rendered to and then sampled like any other texture.
+    /// It uses the surface format so pipelines created for the window can target it.
+    /// Anything blended onto its transparent background ends up premultiplied.
+    pub(crate) fn create_render_target(window: &Window, width: u32, height: u32) -> Self {
+        let settings = TextureSettings {
+            filter: TextureFilter::Linear,
+            premultiply_alpha: true,
+            ..Default::default()
+        };
 
         let size = wgpu::Extent3d {
-            width: rgba.width(),
-            height: rgba.height(),
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
         };
 
         let texture = window.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Texture"),
+            label: Some("Render Target Texture"),
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            view_formats: &[
-                wgpu::TextureFormat::Rgba8UnormSrgb,
-                wgpu::TextureFormat::Rgba8Unorm,
-            ],
-            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+            format: window.config.format,
+            view_formats: &[],
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
         });
 
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = Self::create_sampler(&window.device, &settings);
+
+        let bind_group = Self::create_bind_group(&window.device, &view, &sampler);
+
+        Self {
+            texture,
+            view,
+            sampler,
+            bind_group,
+            size,
+            settings,
+            dynamic: false,
+        }
+    }
+
+    /// Width and height in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.size.width, self.size.height)
+    }
+
+    /// The settings the texture was created with.
+    pub fn settings(&self) -> &TextureSettings {
+        &self.settings
+    }
+
+    pub fn save(&self, window: &Window) -> anyhow::Result<()> {
+        unimplemented!("Saving textures is not yet implemented")
+    }
+
+    /// Loads a texture from the bytes of an image file with the default settings.
+    /// Any format supported by the `image` crate works, such as PNG, JPEG, BMP, GIF, TGA or WebP.
+    pub fn from_bytes(window: &Window, bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::from_bytes_with_settings(window, bytes, TextureSettings::default())
+    }
+
+    /// Loads a texture from the bytes of an image file.
+    /// The format is detected from the contents.
+    pub fn from_bytes_with_settings(
+        window: &Window,
+        bytes: &[u8],
+        settings: TextureSettings,
+    ) -> anyhow::Result<Self> {
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to decode texture: {}", e))?;
+
+        Self::from_image(&window.device, &window.queue, img.into_rgba8(), settings, false)
+    }
+
+    /// Loads a texture from an image file with the default settings.
+    pub fn from_path<P>(window: &Window, path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Self::from_path_with_settings(window, path, TextureSettings::default())
+    }
+
+    /// Loads a texture from an image file.
+    /// The format is detected from the contents, falling back to the file extension.
+    pub fn from_path_with_settings<P>(
+        window: &Window,
+        path: P,
+        settings: TextureSettings,
+    ) -> anyhow::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+
+        let img = image::io::Reader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| anyhow::anyhow!("Failed to open texture '{}': {}", path.display(), e))?
+            .decode()
+            .map_err(|e| anyhow::anyhow!("Failed to decode texture '{}': {}", path.display(), e))?;
+
+        Self::from_image(&window.device, &window.queue, img.into_rgba8(), settings, false)
+    }
+
+    /// Creates a texture from raw 8 bit RGBA pixels, row by row starting at the top left.
+    pub fn from_rgba(
+        window: &Window,
+        width: u32,
+        height: u32,
+        data: &[u8],
+        settings: TextureSettings,
+    ) -> anyhow::Result<Self> {
+        let rgba = Self::rgba_image(width, height, data)?;
+
+        Self::from_image(&window.device, &window.queue, rgba, settings, false)
+    }
+
+    /// Creates a transparent texture whose pixels can be changed from the CPU with
+    /// `Texture::write` and `Texture::write_region`, for things like minimaps or procedural art.
+    /// Writing only needs a shared reference, so a dynamic texture can be updated through an `Asset`.
+    pub fn new_dynamic(
+        window: &Window,
+        width: u32,
+        height: u32,
+        settings: TextureSettings,
+    ) -> anyhow::Result<Self> {
+        // Checked before the pixels are allocated
+        Self::check_size(&window.device, width, height)?;
+
+        let rgba = image::RgbaImage::new(width, height);
+
+        Self::from_image(&window.device, &window.queue, rgba, settings, true)
+    }
+
+    /// True if the texture was created with `Texture::new_dynamic`.
+    pub fn is_dynamic(&self) -> bool {
+        self.dynamic
+    }
+
+    /// Replaces every pixel of a dynamic texture with raw 8 bit RGBA data.
+    /// Mipmaps are regenerated if the texture has them.
+    pub fn write(&self, window: &Window, data: &[u8]) -> anyhow::Result<()> {
+        self.check_dynamic()?;
+
+        let mut rgba = Self::rgba_image(self.size.width, self.size.height, data)?;
+
+        if self.settings.premultiply_alpha {
+            premultiply(&mut rgba);
+        }
+
+        self.write_levels(&window.queue, &rgba);
+
+        Ok(())
+    }
+
+    /// Replaces a rectangle of pixels of a dynamic texture with raw 8 bit RGBA data.
+    /// Only the full size image is updated, use `Texture::write` to refresh the mipmaps.
+    pub fn write_region(
+        &self,
+        window: &Window,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> anyhow::Result<()> {
+        self.check_dynamic()?;
+
+        let inside = |start: u32, length: u32, size: u32| {
+            start.checked_add(length).map(|end| end <= size).unwrap_or(false)
+        };
+
+        if !inside(x, width, self.size.width) || !inside(y, height, self.size.height) {
+            return Err(anyhow::anyhow!(
+                "Region {}x{} at ({}, {}) is outside of the {}x{} texture.",
+                width,
+                height,
+                x,
+                y,
+                self.size.width,
+                self.size.height
+            ));
+        }
+
+        let mut rgba = Self::rgba_image(width, height, data)?;
+
+        if self.settings.premultiply_alpha {
+            premultiply(&mut rgba);
+        }
+
         window.queue.write_texture(
             wgpu::ImageCopyTexture {
-                texture: &texture,
+                texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x, y, z: 0 },
                 aspect: wgpu::TextureAspect::All,
             },
             &rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * rgba.width()),
-                rows_per_image: Some(rgba.height()),
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
             },
-            size,
         );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(())
+    }
 
-        let sampler = window.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Texture Sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+    fn check_dynamic(&self) -> anyhow::Result<()> {
+        if !self.dynamic {
+            return Err(anyhow::anyhow!(
+                "Only textures created with Texture::new_dynamic can be written to."
+            ));
+        }
 
-        let bind_group_layout = Self::get_bind_group_layout(&window.device);
+        Ok(())
+    }
 
-        let bind_group = window.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Texture Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
+    /// Fails for sizes the GPU can't create a texture with, instead of letting wgpu panic.
+    fn check_size(device: &wgpu::Device, width: u32, height: u32) -> anyhow::Result<()> {
+        if width == 0 || height == 0 {
+            return Err(anyhow::anyhow!(
+                "Texture size must not be zero, got {}x{}.",
+                width,
+                height
+            ));
+        }
+
+        let max = device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(anyhow::anyhow!(
+                "Texture is {}x{}, the GPU supports at most {}x{}.",
+                width,
+                height,
+                max,
+                max
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn rgba_image(width: u32, height: u32, data: &[u8]) -> anyhow::Result<image::RgbaImage> {
+        let expected = width as usize * height as usize * 4;
+
+        if data.len() != expected {
+            return Err(anyhow::anyhow!(
+                "Expected {} bytes of RGBA data for a {}x{} texture, got {}.",
+                expected,
+                width,
+                height,
+                data.len()
+            ));
+        }
+
+        image::RgbaImage::from_raw(width, height, data.to_vec())
+            .ok_or(anyhow::anyhow!("Invalid RGBA data."))
+    }
+
+    fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut rgba: image::RgbaImage,
+        settings: TextureSettings,
+        dynamic: bool,
+    ) -> anyhow::Result<Self> {
+        Self::check_size(device, rgba.width(), rgba.height())?;
+
+        if settings.premultiply_alpha {
+            premultiply(&mut rgba);
+        }
+
+        let size = wgpu::Extent3d {
+            width: rgba.width(),
+            height: rgba.height(),
+            depth_or_array_layers: 1,
+        };
+
+        // One level per halving of the largest side, down to 1x1
+        let mip_level_count = if settings.generate_mipmaps {
+            32 - size.width.max(size.height).leading_zeros()
+        } else {
+            1
+        };
+
+        let format = if settings.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Texture"),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            view_formats: &[
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                wgpu::TextureFormat::Rgba8Unorm,
             ],
+            usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
         });
 
-        Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = Self::create_sampler(device, &settings);
+
+        let bind_group = Self::create_bind_group(device, &view, &sampler);
+
+        let texture = Self {
             texture,
             view,
             sampler,
             bind_group,
             size,
+            settings,
+            dynamic,
+        };
+
+        texture.write_levels(queue, &rgba);
+
+        Ok(texture)
+    }
+
+    /// Uploads an already premultiplied image to every mip level.
+    fn write_levels(&self, queue: &wgpu::Queue, rgba: &image::RgbaImage) {
+        for mip_level in 0..self.texture.mip_level_count() {
+            // Every level is resized from the full image to avoid accumulating blur
+            let level = if mip_level == 0 {
+                rgba.clone()
+            } else {
+                image::imageops::resize(
+                    rgba,
+                    (self.size.width >> mip_level).max(1),
+                    (self.size.height >> mip_level).max(1),
+                    image::imageops::FilterType::Triangle,
+                )
+            };
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level.width()),
+                    rows_per_image: Some(level.height()),
+                },
+                wgpu::Extent3d {
+                    width: level.width(),
+                    height: level.height(),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+}
+
+/// Loads image files with `AssetManager::load`.
+/// Register a loader with other settings to change how loaded textures are imported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextureLoader {
+    pub settings: TextureSettings,
+}
+
+impl TextureLoader {
+    pub fn new(settings: TextureSettings) -> Self {
+        Self { settings }
+    }
+}
+
+impl AssetLoader for TextureLoader {
+    type Asset = Texture;
+    type Decoded = image::RgbaImage;
+
+    fn decode(&self, bytes: &[u8], context: &LoadContext) -> Result<image::RgbaImage, AssetError> {
+        let img = image::load_from_memory(bytes).map_err(|e| context.decode_error(e))?;
+
+        Ok(img.into_rgba8())
+    }
+
+    fn create(&self, rgba: image::RgbaImage, context: &LoadContext) -> Result<Texture, AssetError> {
+        Texture::from_image(
+            context.device(),
+            context.queue(),
+            rgba,
+            self.settings,
+            false,
+        )
+        .map_err(|e| context.decode_error(e))
+    }
+}
+
+fn premultiply(rgba: &mut image::RgbaImage) {
+    for pixel in rgba.pixels_mut() {
+        let alpha = pixel[3] as u32;
+
+        for channel in 0..3 {
+            pixel[channel] = ((pixel[channel] as u32 * alpha + 127) / 255) as u8;
         }
     }
 }
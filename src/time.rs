@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use crate::App;
+
+/// Game time, a resource the engine updates at the start of every frame.
+/// Gameplay, physics, timers and tweens use the scaled time, which follows `time_scale`
+/// and stops while paused. UI and anything else that has to keep going uses the real time.
+pub struct Time {
+    last_update: Option<Instant>,
+    delta: Duration,
+    real_delta: Duration,
+    elapsed: Duration,
+    real_elapsed: Duration,
+    frame_count: u64,
+    time_scale: f32,
+    paused: bool,
+    max_delta: Duration,
+}
+
+impl Time {
+    pub(crate) fn new() -> Self {
+        Self {
+            last_update: None,
+            delta: Duration::ZERO,
+            real_delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            real_elapsed: Duration::ZERO,
+            frame_count: 0,
+            time_scale: 1.0,
+            paused: false,
+            max_delta: Duration::from_millis(250),
+        }
+    }
+
+    /// Scaled time since the last frame, zero while paused.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Time since the last frame, ignoring the time scale and pause.
+    pub fn real_delta(&self) -> Duration {
+        self.real_delta
+    }
+
+    pub fn real_delta_seconds(&self) -> f32 {
+        self.real_delta.as_secs_f32()
+    }
+
+    /// Scaled time since the first frame.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Time since the first frame.
+    pub fn real_elapsed(&self) -> Duration {
+        self.real_elapsed
+    }
+
+    /// Frames since the app started, counting the current one. Keeps counting while paused.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Speeds up or slows down game time, e.g. 0.25 for slow motion.
+    /// Negative scales are treated as zero.
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    /// Freezes game time from the next frame on, real time keeps going.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The longest a scaled frame can be, so a hitch, e.g. while the window is dragged,
+    /// doesn't make physics and gameplay jump ahead. 250 ms by default.
+    pub fn set_max_delta(&mut self, max_delta: Duration) {
+        self.max_delta = max_delta;
+    }
+
+    pub fn max_delta(&self) -> Duration {
+        self.max_delta
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+
+        // The first frame has no time since the last one
+        self.real_delta = self
+            .last_update
+            .map(|last_update| now - last_update)
+            .unwrap_or(Duration::ZERO);
+        self.last_update = Some(now);
+
+        self.delta = if self.paused {
+            Duration::ZERO
+        } else {
+            self.real_delta.min(self.max_delta).mul_f32(self.time_scale)
+        };
+
+        self.real_elapsed += self.real_delta;
+        self.elapsed += self.delta;
+        self.frame_count += 1;
+    }
+}
+
+/// Advances the time resource, recreating it if it was removed.
+/// Returns the scaled and the real delta of the frame.
+pub(crate) fn update(app: &mut App) -> (Duration, Duration) {
+    if app.resources.get_resource::<Time>().is_none() {
+        log::warn!("Time resource was removed, creating a new one.");
+
+        if let Err(e) = app.resources.create_resource(Time::new()) {
+            log::error!("Failed to create time resource: {}", e);
+            return (Duration::ZERO, Duration::ZERO);
+        }
+    }
+
+    let Some(time) = app.resources.get_resource_mut::<Time>() else {
+        return (Duration::ZERO, Duration::ZERO);
+    };
+
+    time.update();
+
+    (time.delta, time.real_delta)
+}
@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use hecs::Entity;
+
+use crate::App;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Finishes once and stays finished until reset
+    Once,
+    /// Starts over every time it finishes
+    Repeating,
+}
+
+/// Counts down a duration, e.g. a cooldown or a spawn interval.
+/// As a component it is ticked by the engine every frame before `Stage::PreUpdate`,
+/// in the current scene and additive scenes, and sends `TimerFinished` when it finishes.
+/// It follows the game time of `time::Time`, so it scales with the time scale and stops while paused.
+/// Timers kept anywhere else, e.g. in a resource, are ticked with `tick`, usually by `Time::delta`.
+#[derive(Debug, Clone)]
+pub struct Timer {
+    duration: Duration,
+    elapsed: Duration,
+    mode: TimerMode,
+    paused: bool,
+    /// Multiplies the time passed to `tick`
+    speed: f32,
+    /// Ticked by real time instead of game time
+    real_time: bool,
+    finished: bool,
+    /// How often the timer finished during the last tick
+    times_finished: u32,
+}
+
+impl Timer {
+    pub fn new(duration: Duration, mode: TimerMode) -> Self {
+        Self {
+            duration,
+            elapsed: Duration::ZERO,
+            mode,
+            paused: false,
+            speed: 1.0,
+            real_time: false,
+            finished: false,
+            times_finished: 0,
+        }
+    }
+
+    pub fn once(duration: Duration) -> Self {
+        Self::new(duration, TimerMode::Once)
+    }
+
+    pub fn repeating(duration: Duration) -> Self {
+        Self::new(duration, TimerMode::Repeating)
+    }
+
+    /// Runs the timer faster or slower, e.g. 0.5 for half speed.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Negative speeds are treated as zero.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Ticks the component by real time, so it ignores the time scale and keeps going while paused, e.g. for UI.
+    pub fn with_real_time(mut self) -> Self {
+        self.real_time = true;
+        self
+    }
+
+    pub fn uses_real_time(&self) -> bool {
+        self.real_time
+    }
+
+    /// Advances the timer by `delta` scaled by its speed, unless it is paused.
+    /// A repeating timer can finish more than once in one tick, see `times_finished`.
+    pub fn tick(&mut self, delta: Duration) -> &Self {
+        self.times_finished = 0;
+
+        if self.paused {
+            return self;
+        }
+
+        if self.mode == TimerMode::Once && self.finished {
+            return self;
+        }
+
+        self.elapsed += delta.mul_f32(self.speed);
+
+        if self.elapsed < self.duration {
+            return self;
+        }
+
+        match self.mode {
+            TimerMode::Once => {
+                self.elapsed = self.duration;
+                self.finished = true;
+                self.times_finished = 1;
+            }
+            TimerMode::Repeating => {
+                if self.duration.is_zero() {
+                    self.elapsed = Duration::ZERO;
+                    self.times_finished = 1;
+                } else {
+                    let elapsed = self.elapsed.as_nanos();
+                    let duration = self.duration.as_nanos();
+
+                    // The time past the last finish carries over to the next round
+                    self.elapsed = Duration::from_nanos((elapsed % duration) as u64);
+                    self.times_finished = (elapsed / duration).min(u32::MAX as u128) as u32;
+                }
+                self.finished = true;
+            }
+        }
+
+        self
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Starts the timer over, it keeps its paused state.
+    pub fn reset(&mut self) {
+        self.elapsed = Duration::ZERO;
+        self.finished = false;
+        self.times_finished = 0;
+    }
+
+    /// For a one shot timer, true from the tick it finished on until it is reset.
+    /// For a repeating timer, true if it finished during the last tick.
+    pub fn finished(&self) -> bool {
+        match self.mode {
+            TimerMode::Once => self.finished,
+            TimerMode::Repeating => self.times_finished > 0,
+        }
+    }
+
+    /// True if the timer finished during the last tick.
+    pub fn just_finished(&self) -> bool {
+        self.times_finished > 0
+    }
+
+    /// How often the timer finished during the last tick, more than once for short repeating timers.
+    pub fn times_finished(&self) -> u32 {
+        self.times_finished
+    }
+
+    pub fn mode(&self) -> TimerMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: TimerMode) {
+        self.mode = mode;
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Changes the duration without resetting the elapsed time.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.duration.saturating_sub(self.elapsed)
+    }
+
+    /// How far along the timer is, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+}
+
+/// Sent to `App::events` on every tick a `Timer` component finishes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerFinished {
+    pub scene: String,
+    pub entity: Entity,
+    /// How often it finished during the tick, more than once for short repeating timers
+    pub times: u32,
+}
+
+/// Ticks the timer components of the scenes that run this frame.
+/// Timers in scenes paused under a pushed scene keep their time.
+pub(crate) fn update_timers(app: &mut App, delta: Duration, real_delta: Duration) {
+    let App { scenes, events, .. } = app;
+
+    for (name, scene) in scenes.active_scenes_mut() {
+        for (entity, timer) in scene.world.query_mut::<&mut Timer>() {
+            let delta = if timer.real_time { real_delta } else { delta };
+
+            let times = timer.tick(delta).times_finished();
+
+            if times > 0 {
+                events.send(TimerFinished {
+                    scene: name.to_string(),
+                    entity,
+                    times,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeating_timer_carries_over_the_extra_time() {
+        let mut timer = Timer::repeating(Duration::from_secs(1));
+
+        timer.tick(Duration::from_millis(2500));
+        assert_eq!(timer.times_finished(), 2);
+        assert_eq!(timer.elapsed(), Duration::from_millis(500));
+        assert!(timer.finished());
+
+        timer.tick(Duration::from_millis(250));
+        assert_eq!(timer.times_finished(), 0);
+        assert_eq!(timer.elapsed(), Duration::from_millis(750));
+        assert!(!timer.finished());
+    }
+
+    #[test]
+    fn once_timer_stays_finished() {
+        let mut timer = Timer::once(Duration::from_secs(1));
+
+        timer.tick(Duration::from_secs(2));
+        assert!(timer.just_finished());
+        assert_eq!(timer.elapsed(), Duration::from_secs(1));
+
+        timer.tick(Duration::from_secs(1));
+        assert!(timer.finished());
+        assert!(!timer.just_finished());
+
+        timer.reset();
+        assert!(!timer.finished());
+    }
+
+    #[test]
+    fn paused_timer_keeps_its_time() {
+        let mut timer = Timer::once(Duration::from_secs(1));
+        timer.pause();
+
+        timer.tick(Duration::from_secs(2));
+        assert_eq!(timer.elapsed(), Duration::ZERO);
+        assert!(!timer.finished());
+    }
+}
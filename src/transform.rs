@@ -6,12 +6,22 @@ pub struct Transform {
     rotation: ultraviolet::Rotor3,
     scale: ultraviolet::Vec3,
 
+    // The matrix is rebuilt after loading, so renderables pick up the loaded transform
+    #[serde(skip, default = "dirty_on_load")]
     dirty: bool,
+    #[serde(skip, default = "ultraviolet::Mat4::identity")]
     model_matrix: ultraviolet::Mat4,
+    /// World matrix of the parent entity, kept up to date by the hierarchy
+    #[serde(skip, default = "ultraviolet::Mat4::identity")]
+    parent_matrix: ultraviolet::Mat4,
     /// If true, the transform is relative to the camera
     pub camera_relative: bool,
 }
 
+fn dirty_on_load() -> bool {
+    true
+}
+
 impl Transform {
     /// Returns a reference to the model matrix.
     /// Does not automatically recalculate the model matrix.
@@ -22,9 +32,7 @@ impl Transform {
     /// Recalculates the model matrix.
     pub(crate) fn recalculate_matrix(&mut self) -> bool {
         if self.dirty {
-            self.model_matrix = ultraviolet::Mat4::from_translation(self.position)
-                * self.rotation.into_matrix().into_homogeneous()
-                * ultraviolet::Mat4::from_nonuniform_scale(self.scale);
+            self.model_matrix = self.world_matrix();
 
             self.dirty = false;
             
@@ -45,6 +53,7 @@ impl Transform {
             scale,
             dirty: true,
             model_matrix: ultraviolet::Mat4::identity(),
+            parent_matrix: ultraviolet::Mat4::identity(),
             camera_relative: false,
         }
     }
@@ -93,4 +102,49 @@ impl Transform {
     pub fn up(&self) -> ultraviolet::Vec3 {
         self.rotation * ultraviolet::Vec3::unit_y()
     }
+
+    /// The matrix of the position, rotation and scale, relative to the parent if there is one.
+    pub fn local_matrix(&self) -> ultraviolet::Mat4 {
+        ultraviolet::Mat4::from_translation(self.position)
+            * self.rotation.into_matrix().into_homogeneous()
+            * ultraviolet::Mat4::from_nonuniform_scale(self.scale)
+    }
+
+    /// The matrix of the transform in world space, including all of its parents.
+    /// Parents are applied once per frame before physics and rendering, and again after physics moved the bodies.
+    pub fn world_matrix(&self) -> ultraviolet::Mat4 {
+        self.parent_matrix * self.local_matrix()
+    }
+
+    pub fn world_position(&self) -> ultraviolet::Vec3 {
+        self.world_matrix().cols[3].truncated()
+    }
+
+    /// Sets the world matrix of the parent, marking the transform as dirty if it changed.
+    pub(crate) fn set_parent_matrix(&mut self, parent_matrix: ultraviolet::Mat4) {
+        if self.parent_matrix != parent_matrix {
+            self.parent_matrix = parent_matrix;
+            self.dirty = true;
+        }
+    }
+
+    pub(crate) fn parent_matrix(&self) -> &ultraviolet::Mat4 {
+        &self.parent_matrix
+    }
+
+    /// Sets the position, rotation and scale from a matrix.
+    /// Shear, e.g. from rotated children of non uniformly scaled parents, is lost.
+    pub(crate) fn set_local_matrix(&mut self, matrix: &ultraviolet::Mat4) {
+        let x = matrix.cols[0].truncated();
+        let y = matrix.cols[1].truncated();
+        let z = matrix.cols[2].truncated();
+
+        let scale = ultraviolet::Vec3::new(x.mag(), y.mag(), z.mag());
+        let rotation = ultraviolet::Mat3::new(x / scale.x, y / scale.y, z / scale.z);
+
+        self.position = matrix.cols[3].truncated();
+        self.rotation = rotation.into_rotor3().normalized();
+        self.scale = scale;
+        self.dirty = true;
+    }
 }
@@ -0,0 +1,357 @@
+use std::{sync::Arc, time::Duration};
+
+use hecs::Entity;
+use ultraviolet::{Rotor3, Slerp, Vec3};
+
+use crate::{commands, transform::Transform, App};
+
+pub mod easing;
+pub use easing::Easing;
+
+type CompleteHook = Arc<dyn Fn(&mut App, &TweenCompleted) + Send + Sync>;
+
+/// A property a tween animates, from one value to another.
+/// Tweening a component the entity doesn't have does nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TweenTarget {
+    Position { from: Vec3, to: Vec3 },
+    /// Takes the shortest way between the rotations
+    Rotation { from: Rotor3, to: Rotor3 },
+    Scale { from: Vec3, to: Vec3 },
+    #[cfg(feature = "renderer_2d")]
+    RectColor {
+        from: (f32, f32, f32, f32),
+        to: (f32, f32, f32, f32),
+    },
+    #[cfg(feature = "renderer_2d")]
+    TextColor {
+        from: (f32, f32, f32, f32),
+        to: (f32, f32, f32, f32),
+    },
+    #[cfg(feature = "renderer_2d")]
+    TextFontSize { from: f32, to: f32 },
+}
+
+impl TweenTarget {
+    /// Sets the property to the value `t` of the way between `from` and `to`.
+    fn apply(&self, world: &hecs::World, entity: Entity, t: f32) {
+        match *self {
+            TweenTarget::Position { from, to } => {
+                if let Ok(mut transform) = world.get::<&mut Transform>(entity) {
+                    *transform.position_mut() = lerp_vec(from, to, t);
+                }
+            }
+            TweenTarget::Rotation { from, to } => {
+                if let Ok(mut transform) = world.get::<&mut Transform>(entity) {
+                    *transform.rotation_mut() = from.slerp(to, t);
+                }
+            }
+            TweenTarget::Scale { from, to } => {
+                if let Ok(mut transform) = world.get::<&mut Transform>(entity) {
+                    *transform.scale_mut() = lerp_vec(from, to, t);
+                }
+            }
+            #[cfg(feature = "renderer_2d")]
+            TweenTarget::RectColor { from, to } => {
+                if let Ok(mut rect) = world.get::<&mut crate::renderer_2d::renderables::Rect>(entity) {
+                    rect.set_color(lerp_color(from, to, t));
+                }
+            }
+            #[cfg(feature = "renderer_2d")]
+            TweenTarget::TextColor { from, to } => {
+                if let Ok(mut text) = world.get::<&mut crate::renderer_2d::renderables::Text>(entity) {
+                    text.set_color(lerp_color(from, to, t));
+                }
+            }
+            #[cfg(feature = "renderer_2d")]
+            TweenTarget::TextFontSize { from, to } => {
+                if let Ok(mut text) = world.get::<&mut crate::renderer_2d::renderables::Text>(entity) {
+                    text.set_font_size(from + (to - from) * t);
+                }
+            }
+        }
+    }
+}
+
+fn lerp_vec(from: Vec3, to: Vec3, t: f32) -> Vec3 {
+    from + (to - from) * t
+}
+
+#[cfg(feature = "renderer_2d")]
+fn lerp_color(from: (f32, f32, f32, f32), to: (f32, f32, f32, f32), t: f32) -> (f32, f32, f32, f32) {
+    (
+        from.0 + (to.0 - from.0) * t,
+        from.1 + (to.1 - from.1) * t,
+        from.2 + (to.2 - from.2) * t,
+        from.3 + (to.3 - from.3) * t,
+    )
+}
+
+/// One step of a tween, its targets are animated together.
+#[derive(Debug, Clone)]
+struct TweenStep {
+    targets: Vec<TweenTarget>,
+    duration: Duration,
+    easing: Easing,
+}
+
+/// Animates properties of the entity it is added to, ticked by the engine every frame
+/// after `Stage::PostUpdate`, in the current scene and additive scenes.
+/// It follows the game time of `time::Time` unless it uses real time.
+/// Steps added with `then` play one after the other. Once the tween is done it is removed
+/// from the entity, `TweenCompleted` is sent and the `on_complete` callback runs.
+#[derive(Clone)]
+pub struct Tween {
+    steps: Vec<TweenStep>,
+    step: usize,
+    elapsed: Duration,
+    /// How often the steps play, None to loop forever
+    plays: Option<u32>,
+    played: u32,
+    paused: bool,
+    speed: f32,
+    /// Ticked by real time instead of game time
+    real_time: bool,
+    on_complete: Option<CompleteHook>,
+}
+
+impl Tween {
+    pub fn new(target: TweenTarget, duration: Duration, easing: Easing) -> Self {
+        Self::from_step(TweenStep {
+            targets: vec![target],
+            duration,
+            easing,
+        })
+    }
+
+    /// A tween that starts with a pause, e.g. to stagger several tweens.
+    pub fn delay(duration: Duration) -> Self {
+        Self::from_step(TweenStep {
+            targets: Vec::new(),
+            duration,
+            easing: Easing::Linear,
+        })
+    }
+
+    fn from_step(step: TweenStep) -> Self {
+        Self {
+            steps: vec![step],
+            step: 0,
+            elapsed: Duration::ZERO,
+            plays: Some(1),
+            played: 0,
+            paused: false,
+            speed: 1.0,
+            real_time: false,
+            on_complete: None,
+        }
+    }
+
+    /// Animates another property together with the last step, over the same time.
+    pub fn with(mut self, target: TweenTarget) -> Self {
+        if let Some(step) = self.steps.last_mut() {
+            step.targets.push(target);
+        }
+        self
+    }
+
+    /// Adds a step that plays after the last one.
+    pub fn then(mut self, target: TweenTarget, duration: Duration, easing: Easing) -> Self {
+        self.steps.push(TweenStep {
+            targets: vec![target],
+            duration,
+            easing,
+        });
+        self
+    }
+
+    /// Adds a pause after the last step.
+    pub fn then_wait(mut self, duration: Duration) -> Self {
+        self.steps.push(TweenStep {
+            targets: Vec::new(),
+            duration,
+            easing: Easing::Linear,
+        });
+        self
+    }
+
+    /// Plays the steps `times` times in total.
+    pub fn repeat(mut self, times: u32) -> Self {
+        self.plays = Some(times.max(1));
+        self
+    }
+
+    /// Plays the steps until the tween is removed, it never completes.
+    pub fn looping(mut self) -> Self {
+        self.plays = None;
+        self
+    }
+
+    /// Plays the tween faster or slower, e.g. 0.5 for half speed.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.set_speed(speed);
+        self
+    }
+
+    /// Plays by real time, so it ignores the time scale and keeps going while paused, e.g. for UI.
+    pub fn with_real_time(mut self) -> Self {
+        self.real_time = true;
+        self
+    }
+
+    /// Called once the tween is done, after it was removed from the entity.
+    pub fn on_complete(mut self, f: impl Fn(&mut App, &TweenCompleted) + Send + Sync + 'static) -> Self {
+        self.on_complete = Some(Arc::new(f));
+        self
+    }
+
+    /// Negative speeds are treated as zero.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.0);
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn uses_real_time(&self) -> bool {
+        self.real_time
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// True once the last play has finished.
+    pub fn is_finished(&self) -> bool {
+        self.plays.map(|plays| self.played >= plays).unwrap_or(false)
+    }
+
+    /// The total time of one play through the steps.
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+
+    /// Advances the tween and applies its targets, returns true if it finished.
+    fn tick(&mut self, delta: Duration, world: &hecs::World, entity: Entity) -> bool {
+        if self.paused || self.is_finished() {
+            return self.is_finished();
+        }
+
+        // A tween without length can't advance, so it finishes right away however often it repeats
+        if self.duration().is_zero() {
+            for step in &self.steps {
+                for target in &step.targets {
+                    target.apply(world, entity, step.easing.apply(1.0));
+                }
+            }
+
+            self.plays = Some(1);
+            self.played = 1;
+            return true;
+        }
+
+        let mut remaining = delta.mul_f32(self.speed);
+
+        // Whole plays that fit in the delta are skipped, so a long stall can't spin through
+        // thousands of them. The last play always runs so its final values are applied
+        let total = self.duration();
+        let whole_plays = (remaining.as_nanos() / total.as_nanos()).min(u32::MAX as u128) as u32;
+        let skipped = match self.plays {
+            Some(plays) => whole_plays.min(plays - self.played - 1),
+            None => whole_plays,
+        };
+
+        if skipped > 0 {
+            self.played = self.played.saturating_add(skipped);
+            remaining -= total * skipped;
+        }
+
+        loop {
+            let step = &self.steps[self.step];
+            let left = step.duration - self.elapsed;
+
+            if remaining < left {
+                self.elapsed += remaining;
+
+                let t = self.elapsed.as_secs_f32() / step.duration.as_secs_f32();
+                for target in &step.targets {
+                    target.apply(world, entity, step.easing.apply(t));
+                }
+
+                return false;
+            }
+
+            // Steps that end during this tick still get their final values
+            remaining -= left;
+            for target in &step.targets {
+                target.apply(world, entity, step.easing.apply(1.0));
+            }
+
+            self.elapsed = Duration::ZERO;
+            self.step += 1;
+
+            if self.step == self.steps.len() {
+                self.step = 0;
+                self.played += 1;
+
+                if self.is_finished() {
+                    return true;
+                }
+            }
+        }
+    }
+}
+
+/// Sent to `App::events` when a `Tween` is done, and passed to its `on_complete` callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweenCompleted {
+    pub scene: String,
+    pub entity: Entity,
+}
+
+/// Ticks the tweens of the scenes that run this frame, then runs the completion callbacks.
+pub(crate) fn update_tweens(app: &mut App, delta: Duration, real_delta: Duration) {
+    let mut completed = Vec::new();
+
+    for (name, scene) in app.scenes.active_scenes_mut() {
+        let world = &mut scene.world;
+
+        let mut finished = Vec::new();
+        for (entity, tween) in world.query::<&mut Tween>().iter() {
+            let delta = if tween.real_time { real_delta } else { delta };
+
+            if tween.tick(delta, world, entity) {
+                finished.push(entity);
+            }
+        }
+
+        for entity in finished {
+            if let Ok(tween) = world.remove_one::<Tween>(entity) {
+                let event = TweenCompleted {
+                    scene: name.to_string(),
+                    entity,
+                };
+
+                completed.push((event, tween.on_complete));
+            }
+        }
+    }
+
+    for (event, on_complete) in completed {
+        if let Some(on_complete) = on_complete {
+            on_complete(app, &event);
+            commands::apply(app);
+        }
+
+        app.events.send(event);
+    }
+}
@@ -1,10 +1,13 @@
+use std::sync::Arc;
+
 use winit::event_loop::EventLoop;
 use pollster::FutureExt;
 
 pub struct Window {
     pub(crate) surface: wgpu::Surface,
-    pub(crate) device: wgpu::Device,
-    pub(crate) queue: wgpu::Queue,
+    // Shared with the asset manager so loaders can create GPU resources
+    pub(crate) device: Arc<wgpu::Device>,
+    pub(crate) queue: Arc<wgpu::Queue>,
     pub(crate) config: wgpu::SurfaceConfiguration,
     pub winit_win: winit::window::Window,
 }
@@ -78,8 +81,8 @@ impl Window {
 
         Ok(Window { 
             surface,
-            device,
-            queue,
+            device: Arc::new(device),
+            queue: Arc::new(queue),
             config,
             winit_win 
         })